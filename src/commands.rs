@@ -1,4 +1,5 @@
-use crate::data::icp;
+use crate::data::{icp, layout::Metronome, sheet::LoopRegion};
+use crate::server;
 use druid::{Selector, WidgetId};
 
 // project
@@ -6,6 +7,12 @@ pub const PROJECT_NEW: Selector = Selector::new("project.new");
 pub const PROJECT_OPEN: Selector = Selector::new("project.open");
 pub const PROJECT_SAVE: Selector = Selector::new("project.save");
 pub const PROJECT_SAVE_AS: Selector = Selector::new("project.save-as");
+pub const PROJECT_EXPORT: Selector = Selector::new("project.export");
+
+// recovery
+pub const AUTOSAVE: Selector = Selector::new("recovery.autosave");
+pub const RECOVERY_RESTORE: Selector = Selector::new("recovery.restore");
+pub const RECOVERY_DISCARD: Selector = Selector::new("recovery.discard");
 
 // history
 pub const HISTORY_SAVE: Selector = Selector::new("history.save");
@@ -18,19 +25,32 @@ pub const LAYOUT_APPLY: Selector = Selector::new("layout.apply");
 pub const OPEN_SETTINGS: Selector = Selector::new("editor.settings");
 pub const SETTINGS_APPLY: Selector = Selector::new("settings-apply");
 
+pub const OPEN_SYNTH_EDITOR: Selector = Selector::new("editor.synth.open");
+pub const SYNTH_APPLY: Selector = Selector::new("synth.apply");
+
+pub const OPEN_HEX_KEYBOARD: Selector = Selector::new("hex-keyboard.open");
+
 pub const SHEET_CHANGED: Selector = Selector::new("sheet-changed");
 pub const LAYOUT_CHANGED: Selector = Selector::new("layout-changed");
 
 pub const REDRAW: Selector = Selector::new("redraw");
 
-pub const BACKEND_SET_AUDIO: Selector = Selector::new("backend.set-audio");
-pub const BACKEND_SET_MPE: Selector<usize> = Selector::new("backend.set-mpe");
+pub const BACKEND_SET_OUTPUTS: Selector = Selector::new("backend.set-outputs");
+pub const SERVER_NOTIFICATION: Selector<server::Notification> = Selector::new("server.notification");
+pub const SHOW_STATUS: Selector<String> = Selector::new("show-status");
+pub const BACKEND_AUDIO_REQUEST_DEVICES: Selector<WidgetId> = Selector::new("backend.audio.request-devices");
 pub const BACKEND_MPE_REQUEST_PORTS: Selector<WidgetId> = Selector::new("backend.mpe.request-ports");
+pub const MIDI_INPUT: Selector<server::midi::input::Message> = Selector::new("midi-input");
+pub const MIDI_INPUT_REQUEST_PORTS: Selector<WidgetId> = Selector::new("midi-input.request-ports");
 
 pub const PLAY_START: Selector = Selector::new("play-start");
 pub const PLAY_STOP: Selector = Selector::new("play-stop");
+/// Where the playback stopped, the held keys end their recorded notes there
+pub const RECORDING_STOPPED: Selector<f64> = Selector::new("recording-stopped");
 pub const ICP: Selector<icp::Event> = Selector::new("icp");
 pub const TEMPO_CHANGED: Selector<f64> = Selector::new("tempo-changed");
+pub const LOOP_CHANGED: Selector<LoopRegion> = Selector::new("loop-changed");
+pub const METRONOME_CHANGED: Selector<Metronome> = Selector::new("metronome-changed");
 
 // marker editor
 pub const MARKER_ADD: Selector<f64> = Selector::new("marker-add");
@@ -3,12 +3,40 @@
 
 pub type NoteId = usize;
 
+// each source of live notes gets its own range of ids, so that one can't stop or retune the notes of another
+// the notes of the sheet use their index in the sheet, the live ranges start far above it
+const LIVE_ID: NoteId = 1 << 24;
+pub const PREVIEW_ID: NoteId = LIVE_ID;
+pub const BOARD_ID: NoteId = LIVE_ID + 1;
+pub const HEX_KEYBOARD_ID: NoteId = LIVE_ID + 2;
+pub const KEYBOARD_ID: NoteId = LIVE_ID + 0x100; // followed by one id per key, up to 0x100 keys
+pub const MIDI_INPUT_ID: NoteId = LIVE_ID + 0x1000; // followed by the 16 * 128 keys of the controller
+
 #[derive(Debug, Clone, Copy)]
 pub struct Note {
 	pub id: NoteId,
 	pub freq: f64,
+	pub pan: f64,      // from -1 (left) to 1 (right)
+	pub pressure: f64, // from 0 to 1
+	pub timbre: f64,   // from 0 to 1, the brightness of the note on MPE synths
+}
+
+impl Note {
+	/// A centered note without expression, for the notes played outside of the sheet
+	pub fn new(id: NoteId, freq: f64) -> Note {
+		Note {
+			id,
+			freq,
+			pan: 0.0,
+			pressure: 0.0,
+			timbre: DEFAULT_TIMBRE,
+		}
+	}
 }
 
+/// The timbre of the notes that don't set one, in the middle like MPE controllers
+pub const DEFAULT_TIMBRE: f64 = 0.5;
+
 #[derive(Debug, Clone, Copy)]
 pub enum Event {
 	NotePlay(Note),
@@ -0,0 +1,16 @@
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+/// Clicks played along with the sheet, following the time patterns of the layout
+#[derive(Debug, Clone, Copy, Default, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Metronome {
+	pub enabled: bool,
+	pub subdivisions: bool, // also clicks on the values of the time pattern inside each beat
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Click {
+	Bar,
+	Beat,
+	Subdivision,
+}
@@ -1,7 +1,10 @@
 use super::sheet::{Note, Pitch};
 use druid::Point;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
+mod metronome;
+pub use metronome::*;
 mod pattern;
 pub use pattern::*;
 
@@ -50,6 +53,50 @@ impl Layout {
 		closest
 	}
 
+	/// Puts the markers in the order of their time, which `get_clicks` relies on
+	pub fn sort_markers(&mut self) {
+		// unstable, as it doesn't allocate
+		self.markers.sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+	}
+
+	/// Adds the clicks of the metronome from `start` included to `end` excluded to `clicks`,
+	/// following the time pattern of each marker
+	pub fn get_clicks(&self, start: f64, end: f64, subdivisions: bool, clicks: &mut Vec<(f64, Click)>) {
+		let markers = &self.markers;
+		for (i, (marker, pattern)) in markers.iter().enumerate() {
+			let pattern = match &pattern.time {
+				Some(pattern) => pattern,
+				None => continue,
+			};
+			// each pattern lasts until the next marker
+			let until = markers.get(i + 1).map_or(f64::INFINITY, |next| next.0);
+			let (from, to) = (start.max(*marker), end.min(until));
+			if from >= to {
+				continue;
+			}
+			let first_beat = (from - marker).floor() as usize;
+			let last_beat = (to - marker).ceil() as usize;
+			for beat in first_beat..last_beat {
+				let beat_time = marker + beat as f64;
+				let click = if pattern.nbeats > 0 && beat % pattern.nbeats == 0 {
+					Click::Bar
+				} else {
+					Click::Beat
+				};
+				let divisions = pattern
+					.values
+					.iter()
+					.filter(|&&x| subdivisions && x > 0.0 && x < 1.0)
+					.map(|&x| (beat_time + x, Click::Subdivision));
+				for (time, click) in std::iter::once((beat_time, click)).chain(divisions) {
+					if time >= from && time < to {
+						clicks.push((time, click));
+					}
+				}
+			}
+		}
+	}
+
 	pub fn quantize_time(&self, time: f64, floor: bool) -> f64 {
 		self.quantize_time_impl(time, floor, None, None)
 	}
@@ -117,3 +164,59 @@ impl Layout {
 		note
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn time_pattern(values: Vec<f64>, nbeats: usize) -> Pattern {
+		Pattern {
+			time: Some(TimePattern { values, nbeats }),
+			freq: None,
+		}
+	}
+
+	fn clicks(layout: &Layout, start: f64, end: f64, subdivisions: bool) -> Vec<(f64, Click)> {
+		let mut clicks = vec![];
+		layout.get_clicks(start, end, subdivisions, &mut clicks);
+		clicks
+	}
+
+	#[test]
+	fn clicks_follow_the_markers() {
+		let layout = Layout {
+			markers: vec![(0.0, time_pattern(vec![0.0, 0.5], 2)), (3.0, time_pattern(vec![0.0], 3))],
+		};
+		assert_eq!(
+			clicks(&layout, 0.0, 4.0, true),
+			vec![
+				(0.0, Click::Bar),
+				(0.5, Click::Subdivision),
+				(1.0, Click::Beat),
+				(1.5, Click::Subdivision),
+				(2.0, Click::Bar),
+				(2.5, Click::Subdivision),
+				(3.0, Click::Bar),
+			]
+		);
+		assert_eq!(clicks(&layout, 0.0, 2.0, false), vec![(0.0, Click::Bar), (1.0, Click::Beat)]);
+	}
+
+	#[test]
+	fn clicks_exclude_the_end() {
+		let mut layout = Layout {
+			markers: vec![(2.0, time_pattern(vec![0.0], 4)), (0.0, time_pattern(vec![0.0], 2))],
+		};
+		layout.sort_markers();
+		assert_eq!(clicks(&layout, 1.0, 3.0, false), vec![(1.0, Click::Beat), (2.0, Click::Bar)]);
+		assert!(clicks(&layout, 0.5, 1.0, false).is_empty());
+	}
+
+	#[test]
+	fn no_clicks_without_a_time_pattern() {
+		let layout = Layout {
+			markers: vec![(0.0, Pattern::EMPTY), (2.0, time_pattern(vec![0.0], 2))],
+		};
+		assert_eq!(clicks(&layout, 0.0, 3.0, false), vec![(2.0, Click::Bar)]);
+	}
+}
@@ -28,6 +28,18 @@ impl FreqPattern {
 	pub fn period(&self) -> f64 {
 		self.values[self.values.len() - 1] / self.values[0]
 	}
+
+	/// The number of degrees in a period, the last value being the period itself
+	pub fn nb_degrees(&self) -> usize {
+		(self.values.len() - 1).max(1)
+	}
+
+	/// The frequency of a degree of the scale, 0 being the base and each period adding `nb_degrees` degrees
+	pub fn degree_freq(&self, degree: i64) -> f64 {
+		let nb_degrees = self.nb_degrees() as i64;
+		let (period, step) = (degree.div_euclid(nb_degrees), degree.rem_euclid(nb_degrees));
+		self.base * self.values[step as usize] / self.values[0] * self.period().powi(period as i32)
+	}
 }
 
 // the elements of each component are assumed to be sorted
@@ -1,3 +1,4 @@
 pub mod icp;
 pub mod layout;
 pub mod sheet;
+pub mod synth;
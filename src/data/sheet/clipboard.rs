@@ -42,6 +42,9 @@ impl Clipboard {
 		let note: Note<Index> = Note {
 			start: sheet_note.start,
 			length: sheet_note.length,
+			pan: sheet_note.pan,
+			pressure: sheet_note.pressure,
+			timbre: sheet_note.timbre,
 			pitch: match sheet_note.pitch {
 				Pitch::Absolute(freq) => Pitch::Absolute(freq),
 				Pitch::Relative(idx, interval) => Pitch::Relative(
@@ -85,6 +88,9 @@ impl Clipboard {
 			let sheet_note = SheetNote {
 				start: note.start,
 				length: note.length,
+				pan: note.pan,
+				pressure: note.pressure,
+				timbre: note.timbre,
 				pitch,
 			};
 			let index = sheet.add_note(sheet_note);
@@ -0,0 +1,32 @@
+use crate::util::Range;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+/// The part of the sheet played over and over, in beats
+#[derive(Debug, Clone, Copy, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct LoopRegion {
+	pub enabled: bool,
+	pub start: f64,
+	pub end: f64,
+}
+
+impl Default for LoopRegion {
+	fn default() -> LoopRegion {
+		LoopRegion {
+			enabled: false,
+			start: 0.0,
+			end: 4.0,
+		}
+	}
+}
+
+impl LoopRegion {
+	/// The range to loop over, if looping is enabled and the region isn't empty
+	pub fn range(&self) -> Option<Range> {
+		if self.enabled && self.start < self.end {
+			Some(Range(self.start, self.end))
+		} else {
+			None
+		}
+	}
+}
@@ -13,6 +13,9 @@ pub mod note;
 mod clipboard;
 pub use clipboard::Clipboard;
 
+mod loop_region;
+pub use loop_region::LoopRegion;
+
 pub type Pitch = note::Pitch<Index>;
 pub type Note = note::Note<Index>;
 
@@ -166,35 +169,45 @@ impl Sheet {
 		}
 	}
 
-	pub fn get_events_at_time(&self, time: f64) -> Vec<icp::Event> {
-		let mut events = vec![];
+	fn icp_note(&self, index: Index, note: &Note) -> icp::Note {
+		icp::Note {
+			id: index.into_raw_parts().0,
+			freq: self.get_freq(note.pitch),
+			pan: note.pan,
+			pressure: note.pressure,
+			timbre: note.timbre,
+		}
+	}
+
+	/// Calls `f` with the notes playing across `time`, which started before it and end after it
+	pub fn for_notes_at_time(&self, time: f64, mut f: impl FnMut(icp::Note)) {
 		for (index, note) in &self.notes {
 			if note.start < time && note.end() > time {
-				let icp_note = icp::Note {
-					id: index.into_raw_parts().0,
-					freq: self.get_freq(note.pitch),
-				};
-				events.push(icp::Event::NotePlay(icp_note));
+				f(self.icp_note(index, note));
+			}
+		}
+	}
+
+	/// Calls `f` with the notes still playing right before `time`, which end at it or after
+	pub fn for_notes_before_time(&self, time: f64, mut f: impl FnMut(icp::Note)) {
+		for (index, note) in &self.notes {
+			if note.start < time && note.end() >= time {
+				f(self.icp_note(index, note));
 			}
 		}
-		events
 	}
 
-	pub fn get_events(&self, range: crate::util::Range) -> Vec<icp::Event> {
-		let mut events = vec![];
+	/// Calls `f` with the events happening from the start of `range` included to its end excluded, along with their time
+	pub fn for_events(&self, range: Range, mut f: impl FnMut(f64, icp::Event)) {
+		// excluding the end, so that an event on the boundary of two consecutive ranges only happens once
+		let contains = |time: f64| range.0 <= time && time < range.1;
 		for (index, note) in &self.notes {
-			let id = index.into_raw_parts().0;
-			if range.contains(note.start) {
-				let icp_note = icp::Note {
-					id,
-					freq: self.get_freq(note.pitch),
-				};
-				events.push(icp::Event::NotePlay(icp_note));
+			if contains(note.start) {
+				f(note.start, icp::Event::NotePlay(self.icp_note(index, note)));
 			}
-			if range.contains(note.end()) {
-				events.push(icp::Event::NoteStop(id));
+			if contains(note.end()) {
+				f(note.end(), icp::Event::NoteStop(index.into_raw_parts().0));
 			}
 		}
-		events
 	}
 }
@@ -1,4 +1,5 @@
 use super::{Interval, Sheet};
+use crate::data::icp;
 use druid::{Point, Rect};
 use serde::{Deserialize, Serialize};
 
@@ -13,6 +14,16 @@ pub struct Note<I> {
 	pub pitch: Pitch<I>,
 	pub start: f64,
 	pub length: f64,
+	#[serde(default)]
+	pub pan: f64, // from -1 (left) to 1 (right)
+	#[serde(default)]
+	pub pressure: f64, // from 0 to 1
+	#[serde(default = "default_timbre")]
+	pub timbre: f64, // from 0 to 1
+}
+
+fn default_timbre() -> f64 {
+	icp::DEFAULT_TIMBRE
 }
 
 #[allow(unused)]
@@ -22,6 +33,9 @@ impl<I> Note<I> {
 			start: pos.x,
 			length: note_len,
 			pitch: Pitch::Absolute(2f64.powf(pos.y)),
+			pan: 0.0,
+			pressure: 0.0,
+			timbre: icp::DEFAULT_TIMBRE,
 		}
 	}
 
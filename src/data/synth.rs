@@ -0,0 +1,443 @@
+//! Sound settings of the integrated synth, saved with the project
+
+use derive_more::Display;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+	pub waveform: Waveform,
+	#[serde(default)]
+	pub partials: Partials,
+	#[serde(default)]
+	pub samples: SampleSet,
+	#[serde(default)]
+	pub wavetable: Wavetable,
+	#[serde(default)]
+	pub operators: Operators,
+	pub envelope: Envelope,
+	pub filter: Filter,
+	#[serde(default)]
+	pub effects: Effects,
+	#[serde(default)]
+	pub voices: Voices,
+	pub gain: f64,
+}
+
+impl Default for Patch {
+	fn default() -> Patch {
+		Patch {
+			waveform: Waveform::Saw,
+			partials: Partials::default(),
+			samples: SampleSet::default(),
+			wavetable: Wavetable::default(),
+			operators: Operators::default(),
+			envelope: Envelope {
+				attack: 0.2,
+				decay: 0.1,
+				sustain: 0.8,
+				release: 0.1,
+			},
+			filter: Filter {
+				kind: FilterKind::Lowpass,
+				cutoff: 880.0,
+				resonance: 0.3,
+			},
+			effects: Effects::default(),
+			voices: Voices::default(),
+			gain: 0.8,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum Waveform {
+	#[display(fmt = "Sine")]
+	Sine,
+	#[display(fmt = "Saw")]
+	Saw,
+	#[display(fmt = "Square")]
+	Square,
+	#[display(fmt = "Additive")]
+	Additive,
+	#[display(fmt = "Sample")]
+	Sample,
+	#[display(fmt = "Wavetable")]
+	Wavetable,
+	#[display(fmt = "FM")]
+	Fm,
+}
+
+/// The partials played by the additive waveform
+#[derive(Debug, Clone, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum Partials {
+	#[display(fmt = "Custom")]
+	Custom { spectrum: Spectrum },
+	/// Harmonics stretched so that the 2nd one falls on the period of the layout,
+	/// which makes the timbre consonant with the scale (see Sethares, "Tuning, Timbre, Spectrum, Scale")
+	#[display(fmt = "Stretched")]
+	Stretched { count: usize },
+}
+
+impl Default for Partials {
+	fn default() -> Partials {
+		Partials::Stretched { count: 8 }
+	}
+}
+
+impl Partials {
+	pub fn spectrum(&self, period: f64) -> Spectrum {
+		match self {
+			Partials::Custom { spectrum } => spectrum.clone(),
+			Partials::Stretched { count } => {
+				let stretch = period.log2();
+				Spectrum(Arc::new(
+					(1..=*count)
+						.map(|n| ((n as f64).powf(stretch), 1.0 / n as f64))
+						.collect(),
+				))
+			}
+		}
+	}
+}
+
+impl Patch {
+	/// Fixes the parts that depend on the layout and the tempo, so that the patch can be played on its own
+	pub fn resolve(&self, period: f64, tempo: f64) -> Patch {
+		let mut delay = self.effects.delay.clone();
+		if delay.synced {
+			delay.synced = false;
+			delay.time = delay.beats * 60.0 / tempo;
+		}
+		Patch {
+			partials: Partials::Custom {
+				spectrum: self.partials.spectrum(period),
+			},
+			envelope: self.envelope.clamped(),
+			filter: self.filter.clamped(),
+			effects: Effects {
+				delay,
+				..self.effects.clone()
+			},
+			..self.clone()
+		}
+	}
+}
+
+/// Pairs of frequency ratio and amplitude, written as `ratio:amplitude` separated by spaces
+#[derive(Debug, Clone, Data, PartialEq, Serialize, Deserialize)]
+pub struct Spectrum(pub Arc<Vec<(f64, f64)>>);
+
+#[derive(Debug, Display)]
+pub struct SpectrumParseError;
+impl std::error::Error for SpectrumParseError {}
+
+impl std::str::FromStr for Spectrum {
+	type Err = SpectrumParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match parse_ratios(s) {
+			Some(partials) if !partials.is_empty() => Ok(Spectrum(Arc::new(partials))),
+			_ => Err(SpectrumParseError),
+		}
+	}
+}
+
+impl std::fmt::Display for Spectrum {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", format_ratios(&self.0))
+	}
+}
+
+// reads `ratio:value` pairs separated by spaces, the ratios must be positive
+fn parse_ratios(s: &str) -> Option<Vec<(f64, f64)>> {
+	s.split_whitespace()
+		.map(|pair| {
+			let mut parts = pair.split(':');
+			let ratio = parts.next().and_then(|x| x.parse::<f64>().ok());
+			let value = parts.next().and_then(|x| x.parse::<f64>().ok());
+			match (ratio, value, parts.next()) {
+				(Some(ratio), Some(value), None) if ratio > 0.0 => Some((ratio, value)),
+				_ => None,
+			}
+		})
+		.collect()
+}
+
+fn format_ratios(pairs: &[(f64, f64)]) -> String {
+	pairs
+		.iter()
+		.map(|(ratio, value)| format!("{}:{}", ratio, value))
+		.collect::<Vec<String>>()
+		.join(" ")
+}
+
+/// Frequency ratio and modulation index of each FM operator, from the carrier to the last modulator.
+/// Each operator modulates the previous one, and the value of the carrier is its output level.
+#[derive(Debug, Clone, Data, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "OperatorsData")]
+pub struct Operators(pub Arc<Vec<(f64, f64)>>);
+
+// what project files contain, before the number of operators is checked
+#[derive(Deserialize)]
+#[serde(rename = "Operators")]
+struct OperatorsData(Vec<(f64, f64)>);
+
+impl TryFrom<OperatorsData> for Operators {
+	type Error = OperatorsParseError;
+
+	fn try_from(data: OperatorsData) -> Result<Self, Self::Error> {
+		if (2..=4).contains(&data.0.len()) {
+			Ok(Operators(Arc::new(data.0)))
+		} else {
+			Err(OperatorsParseError)
+		}
+	}
+}
+
+impl Default for Operators {
+	fn default() -> Operators {
+		Operators(Arc::new(vec![(1.0, 1.0), (1.5, 2.0)]))
+	}
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "FM needs from 2 to 4 operators")]
+pub struct OperatorsParseError;
+impl std::error::Error for OperatorsParseError {}
+
+impl std::str::FromStr for Operators {
+	type Err = OperatorsParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match parse_ratios(s) {
+			Some(operators) => Operators::try_from(OperatorsData(operators)),
+			None => Err(OperatorsParseError),
+		}
+	}
+}
+
+impl std::fmt::Display for Operators {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", format_ratios(&self.0))
+	}
+}
+
+/// A file of consecutive single-cycle waveforms, the position crossfades between them
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Wavetable {
+	pub path: String,
+	pub frame_size: usize,
+	pub position: f64, // between 0 and 1
+}
+
+impl Default for Wavetable {
+	fn default() -> Wavetable {
+		Wavetable {
+			path: String::new(),
+			frame_size: 2048,
+			position: 0.0,
+		}
+	}
+}
+
+/// Durations are in seconds, the sustain is a level between 0 and 1
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+	pub attack: f64,
+	pub decay: f64,
+	pub sustain: f64,
+	pub release: f64,
+}
+
+// the envelope divides by its durations
+const MIN_ENVELOPE_TIME: f64 = 1e-4; // in seconds
+
+impl Envelope {
+	/// The envelope with values the synth can play, whatever was typed in
+	pub fn clamped(&self) -> Envelope {
+		Envelope {
+			attack: self.attack.max(MIN_ENVELOPE_TIME),
+			decay: self.decay.max(MIN_ENVELOPE_TIME),
+			sustain: self.sustain.max(0.0).min(1.0),
+			release: self.release.max(MIN_ENVELOPE_TIME),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+	pub kind: FilterKind,
+	pub cutoff: f64, // in Hz
+	pub resonance: f64,
+}
+
+// the filter has no damping left at a resonance of 1, and blows up above
+const MAX_RESONANCE: f64 = 0.99;
+
+impl Filter {
+	/// The filter with values the synth can play, whatever was typed in
+	pub fn clamped(&self) -> Filter {
+		Filter {
+			resonance: self.resonance.max(0.0).min(MAX_RESONANCE),
+			..self.clone()
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum FilterKind {
+	#[display(fmt = "Lowpass")]
+	Lowpass,
+	#[display(fmt = "Bandpass")]
+	Bandpass,
+	#[display(fmt = "Highpass")]
+	Highpass,
+}
+
+/// How many notes can play at once, and which one is cut when there are too many
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Voices {
+	pub polyphony: usize,
+	pub stealing: Stealing,
+}
+
+impl Default for Voices {
+	fn default() -> Voices {
+		Voices {
+			polyphony: 32,
+			stealing: Stealing::ReleasedFirst,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum Stealing {
+	#[display(fmt = "Oldest")]
+	Oldest,
+	#[display(fmt = "Quietest")]
+	Quietest,
+	/// The oldest of the released notes, or the oldest note if none is released
+	#[display(fmt = "Released first")]
+	ReleasedFirst,
+}
+
+/// Applied in order after the filter
+#[derive(Debug, Clone, Default, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Effects {
+	pub delay: Delay,
+	pub reverb: Reverb,
+}
+
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Delay {
+	pub enabled: bool,
+	pub time: f64, // in seconds
+	/// Uses `beats` instead of `time`, following the tempo of the project
+	pub synced: bool,
+	pub beats: f64,
+	pub feedback: f64,
+	pub mix: f64,
+}
+
+impl Default for Delay {
+	fn default() -> Delay {
+		Delay {
+			enabled: false,
+			time: 0.3,
+			synced: false,
+			beats: 0.75,
+			feedback: 0.4,
+			mix: 0.3,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Data, Lens, PartialEq, Serialize, Deserialize)]
+pub struct Reverb {
+	pub enabled: bool,
+	pub size: f64,    // between 0 and 1
+	pub damping: f64, // between 0 and 1
+	pub mix: f64,
+}
+
+impl Default for Reverb {
+	fn default() -> Reverb {
+		Reverb {
+			enabled: false,
+			size: 0.7,
+			damping: 0.5,
+			mix: 0.25,
+		}
+	}
+}
+
+/// Recordings played at any frequency, each note uses the one with the closest root
+#[derive(Debug, Clone, Default, Data, PartialEq, Serialize, Deserialize)]
+pub struct SampleSet(pub Arc<Vec<SampleZone>>);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleZone {
+	pub root: f64, // in Hz
+	pub path: PathBuf,
+	pub loop_range: Option<(f64, f64)>, // in seconds
+}
+
+#[derive(Debug, Display)]
+pub struct SampleSetParseError;
+impl std::error::Error for SampleSetParseError {}
+
+// zones are written as `root, path[, loop start, loop end]` separated by semicolons
+impl std::str::FromStr for SampleSet {
+	type Err = SampleSetParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let zones = s
+			.split(';')
+			.map(|zone| zone.trim())
+			.filter(|zone| !zone.is_empty())
+			.map(|zone| {
+				let parts = zone.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+				let root = parts[0].parse::<f64>().map_err(|_| SampleSetParseError)?;
+				let loop_range = match parts.len() {
+					2 => None,
+					4 => {
+						let start = parts[2].parse::<f64>().map_err(|_| SampleSetParseError)?;
+						let end = parts[3].parse::<f64>().map_err(|_| SampleSetParseError)?;
+						Some((start, end))
+					}
+					_ => return Err(SampleSetParseError),
+				};
+				if root <= 0.0 || parts[1].is_empty() {
+					return Err(SampleSetParseError);
+				}
+				Ok(SampleZone {
+					root,
+					path: parts[1].into(),
+					loop_range,
+				})
+			})
+			.collect::<Result<Vec<SampleZone>, _>>()?;
+		Ok(SampleSet(Arc::new(zones)))
+	}
+}
+
+impl std::fmt::Display for SampleSet {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(
+			f,
+			"{}",
+			self.0
+				.iter()
+				.map(|zone| match zone.loop_range {
+					Some((start, end)) => format!("{}, {}, {}, {}", zone.root, zone.path.display(), start, end),
+					None => format!("{}, {}", zone.root, zone.path.display()),
+				})
+				.collect::<Vec<String>>()
+				.join("; ")
+		)
+	}
+}
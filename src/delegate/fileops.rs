@@ -1,10 +1,11 @@
-use super::Delegate;
+use super::{synth, Delegate};
 use crate::commands as cmds;
+use crate::server;
 use crate::state::{self, State};
 use crate::ui;
 use crate::widget;
 use druid::{commands as sys_cmds, Command, DelegateCtx, FileDialogOptions, FileSpec, Selector, Target};
-use std::{fs, rc::Rc};
+use std::{fs, rc::Rc, thread};
 
 pub const IMPL_PROJECT_NEW: Selector = Selector::new("delegate.project-new");
 pub const IMPL_PROJECT_OPEN: Selector = Selector::new("delegate.project-open");
@@ -75,9 +76,42 @@ impl Delegate {
 				}
 				false
 			}
+			_ if cmd.is(cmds::PROJECT_EXPORT) => {
+				let wav = FileSpec::new("WAV audio", &["wav"]);
+				ctx.submit_command(
+					sys_cmds::SHOW_SAVE_PANEL
+						.with(FileDialogOptions::new().allowed_types(vec![wav]).default_type(wav))
+						.to(Target::Window(main_window)),
+				);
+				// the same panel saves the project, the answer is told apart with this
+				self.export_pending = true;
+				false
+			}
+			_ if cmd.is(sys_cmds::SAVE_FILE) && self.export_pending => {
+				self.export_pending = false;
+				if let Some(file_info) = cmd.get_unchecked(sys_cmds::SAVE_FILE) {
+					let path = file_info.path().with_extension("wav");
+					let sheet = data.editors.sheet_editor.sheet.borrow().clone();
+					let tempo = data.editors.sheet_editor.tempo;
+					let patch = synth::resolved_patch(data);
+					let to_frontend = self.to_frontend.clone();
+					thread::spawn(move || {
+						let notification = match server::audio::render(sheet, tempo, patch, &path) {
+							Ok(()) => server::Notification::Info(format!("Exported to {}", path.display())),
+							Err(err) => server::Notification::Warning(format!("The export failed: {}", err)),
+						};
+						to_frontend.send(notification).ok();
+					});
+				}
+				false
+			}
 			_ if cmd.is(IMPL_PROJECT_NEW) => {
 				let mut state = State::new();
 				state.main_window = data.main_window.clone();
+				state.meters = data.meters.clone();
+				state.editors.sheet_editor.playhead = data.editors.sheet_editor.playhead.clone();
+				// the settings aren't part of the project
+				state.editors.settings = data.editors.settings.clone();
 				*data = state;
 				*project_changed = true;
 				self.after_save = None;
@@ -116,7 +150,7 @@ impl Delegate {
 						data.save_path = Some(Rc::new(file_info.path().into()));
 						*project_changed = true;
 					} else if let Err(err) = project {
-						println!("The project failed to open: {:?}", err);
+						self.show_error(ctx, data, format!("The project failed to open: {}", err));
 					}
 				}
 				true
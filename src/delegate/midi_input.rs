@@ -0,0 +1,176 @@
+use super::Delegate;
+use crate::commands as cmds;
+use crate::data::{icp, sheet::Note};
+use crate::server::{
+	self,
+	midi::input::{self, Message},
+};
+use crate::state::{editors::settings::KeyMapping, State};
+use druid::{Command, DelegateCtx, Point, Target};
+use std::collections::HashMap;
+use std::error::Error;
+
+fn note_id(channel: u8, key: u8) -> icp::NoteId {
+	icp::MIDI_INPUT_ID + usize::from(channel) * 128 + usize::from(key)
+}
+
+/// The connection to the controller, and the notes it is holding
+#[derive(Default)]
+pub struct Input {
+	ports: Vec<midir::MidiInputPort>,
+	conn: Option<midir::MidiInputConnection<()>>,
+	bends: [f64; 16],                         // in semitones, for each channel
+	monitoring: HashMap<(u8, u8), f64>,       // frequency of the held keys without the bend
+	recording: HashMap<(u8, u8), (f64, f64)>, // start and frequency of the held keys
+}
+
+impl Input {
+	pub fn new(ports: Vec<midir::MidiInputPort>) -> Input {
+		Input {
+			ports,
+			..Default::default()
+		}
+	}
+
+	fn bend(&self, channel: u8) -> f64 {
+		2f64.powf(self.bends[usize::from(channel)] / 12.0)
+	}
+}
+
+impl Delegate {
+	/// Connects to the port chosen in the settings, closing the previous connection
+	pub fn connect_midi_input(&mut self, data: &State) -> Result<(), Box<dyn Error>> {
+		self.input.conn = None;
+		for ((channel, key), _) in self.input.monitoring.drain() {
+			self.server.send(server::Event::ICP(icp::Event::NoteStop(note_id(channel, key))));
+		}
+		self.input.recording.clear();
+		let settings = &data.editors.settings.midi_input;
+		if !settings.enabled {
+			return Ok(());
+		}
+		let port = self.input.ports.get(settings.port.index).ok_or("the MIDI input port is not available")?;
+		let event_sink = self.event_sink.clone();
+		self.input.conn = Some(input::connect(port, move |message| {
+			event_sink.submit_command(cmds::MIDI_INPUT, message, Target::Auto).ok();
+		})?);
+		Ok(())
+	}
+
+	// the frequency of a key before the pitch bend, with the layout at `at`
+	fn key_freq(data: &State, key: u8, at: f64) -> f64 {
+		let settings = &data.editors.settings.midi_input;
+		let tet = 440.0 * 2f64.powf((f64::from(key) - 69.0) / 12.0);
+		match settings.mapping {
+			KeyMapping::Tet => tet,
+			KeyMapping::Layout => {
+				let layout = data.editors.sheet_editor.layout.borrow();
+				match &layout.get_marker_at(at, None).1.freq {
+					Some(pattern) => pattern.degree_freq(i64::from(key) - i64::from(settings.reference_key)),
+					None => tet,
+				}
+			}
+		}
+	}
+
+	/// The position the server is playing at, which is where notes are recorded
+	pub fn position(data: &State) -> f64 {
+		let sheet_editor = &data.editors.sheet_editor;
+		sheet_editor.playhead.read().unwrap_or(sheet_editor.cursor)
+	}
+
+	fn process_midi_input(&mut self, ctx: &mut DelegateCtx, data: &mut State, message: Message) {
+		let sheet_editor = &data.editors.sheet_editor;
+		let recording = sheet_editor.playing && sheet_editor.recording;
+		match message {
+			Message::NoteOn { channel, key, .. } => {
+				let position = Self::position(data);
+				let freq = Self::key_freq(data, key, position);
+				let bent_freq = freq * self.input.bend(channel);
+				if data.editors.settings.midi_input.monitor {
+					self.input.monitoring.insert((channel, key), freq);
+					let note = icp::Note::new(note_id(channel, key), bent_freq);
+					self.server.send(server::Event::ICP(icp::Event::NotePlay(note)));
+				}
+				if recording {
+					self.input.recording.insert((channel, key), (position, bent_freq));
+				}
+			}
+			Message::NoteOff { channel, key } => {
+				if self.input.monitoring.remove(&(channel, key)).is_some() {
+					self.server.send(server::Event::ICP(icp::Event::NoteStop(note_id(channel, key))));
+				}
+				if let Some((start, freq)) = self.input.recording.remove(&(channel, key)) {
+					let end = data.editors.sheet_editor.recorded_end(start, Self::position(data));
+					if end > start {
+						self.record_note(ctx, data, start, end, freq);
+					}
+				}
+			}
+			Message::PitchBend { channel, bend } => {
+				if let Some(x) = self.input.bends.get_mut(usize::from(channel)) {
+					*x = bend * data.editors.settings.midi_input.bend_range;
+				}
+				// an MPE controller bends each note on its own channel
+				let bend = self.input.bend(channel);
+				for (&(ch, key), &freq) in &self.input.monitoring {
+					if ch == channel {
+						let event = icp::Event::NoteChangeFreq(note_id(ch, key), freq * bend);
+						self.server.send(server::Event::ICP(event));
+					}
+				}
+			}
+		}
+	}
+
+	/// Ends the notes of the held keys at `stopped_at`, the keys keep sounding until they are released
+	pub fn stop_midi_recording(&mut self, ctx: &mut DelegateCtx, data: &State, stopped_at: f64) {
+		for (_, (start, freq)) in std::mem::take(&mut self.input.recording) {
+			let end = data.editors.sheet_editor.recorded_end(start, stopped_at);
+			if end > start {
+				self.record_note(ctx, data, start, end, freq);
+			}
+		}
+	}
+
+	fn record_note(&self, ctx: &mut DelegateCtx, data: &State, start: f64, end: f64, freq: f64) {
+		let sheet_editor = &data.editors.sheet_editor;
+		let mut note = Note::new(Point::new(start, freq.log2()), end - start);
+		if data.editors.settings.midi_input.quantize {
+			note = sheet_editor.layout.borrow().quantize_note(note);
+		}
+		sheet_editor.sheet.borrow_mut().add_note(note);
+		ctx.submit_command(cmds::SHEET_CHANGED);
+		ctx.submit_command(cmds::HISTORY_SAVE);
+	}
+
+	pub fn handle_midi_input(
+		&mut self,
+		ctx: &mut DelegateCtx,
+		cmd: &Command,
+		data: &mut State,
+		_project_changed: &mut bool,
+	) -> bool {
+		match cmd {
+			_ if cmd.is(cmds::MIDI_INPUT) => {
+				let message = *cmd.get_unchecked(cmds::MIDI_INPUT);
+				self.process_midi_input(ctx, data, message);
+				false
+			}
+			_ if cmd.is(cmds::MIDI_INPUT_REQUEST_PORTS) => {
+				let target = *cmd.get_unchecked(cmds::MIDI_INPUT_REQUEST_PORTS);
+				let (ports, port_names) = match input::list_ports() {
+					Ok(ports) => ports,
+					Err(err) => {
+						self.show_error(ctx, data, format!("Couldn't list the MIDI input ports: {}", err));
+						return false;
+					}
+				};
+				self.input.ports = ports;
+				super::settings::set_choices(ctx, &mut data.editors.settings.midi_input.port, port_names, target);
+				false
+			}
+			_ => true,
+		}
+	}
+}
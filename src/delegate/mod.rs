@@ -1,36 +1,135 @@
-use druid::{AppDelegate, Command, DelegateCtx, Env, Target, WindowId};
+use druid::{AppDelegate, Command, DelegateCtx, Env, ExtEventSink, LocalizedString, Target, WindowDesc, WindowId};
 
+use std::error::Error;
 use std::rc::Rc;
 use std::sync::mpsc::*;
+use std::thread;
 
 use crate::commands as cmds;
 use crate::server;
-use crate::state::State;
+use crate::state::{self, State};
+use crate::ui;
+use crate::widget;
 
 pub struct Delegate {
-	to_server: Sender<server::Event>,
+	server: server::Server,
+	to_frontend: Sender<server::Notification>,
 	after_save: Option<Box<dyn Fn(&mut DelegateCtx)>>,
+	export_pending: bool, // the save panel is open for the exported audio file
 	midi_ports: Vec<midir::MidiOutputPort>,
+	recovery_pending: bool,
+	startup_error: Option<String>, // shown once the main window is there
+	autosave_failed: bool,         // the error is only shown the first time
+	play_origin: f64, // where the cursor was when playback started
+	event_sink: ExtEventSink,
+	input: midi_input::Input,
 }
 
 impl Delegate {
-	pub fn new(to_server: Sender<server::Event>) -> Delegate {
-		Delegate {
-			to_server,
+	pub fn new(
+		saved: state::editors::settings::State,
+		data: &mut State,
+		event_sink: ExtEventSink,
+	) -> Result<Delegate, Box<dyn Error>> {
+		let (midi_ports, port_names) = settings::list_midi_ports().unwrap_or_default();
+		let device_names = server::audio::list_devices().unwrap_or_default();
+		let (input_ports, input_port_names) = server::midi::input::list_ports().unwrap_or_default();
+
+		data.editors.settings = saved;
+		let settings = &mut data.editors.settings;
+		// a device that isn't there anymore is replaced by the default one
+		let devices = std::iter::once(state::editors::settings::DEFAULT_DEVICE.to_string()).chain(device_names);
+		settings.audio.device.set_names(devices.collect());
+		// reconnect to the saved MIDI port, or fall back to the synth if it isn't there anymore
+		if !settings.mpe.port.set_names(port_names) && settings.mpe.enabled {
+			settings.mpe.enabled = false;
+			settings.synth = true;
+		}
+		if !settings.midi_input.port.set_names(input_port_names) {
+			settings.midi_input.enabled = false;
+		}
+
+		// forward the notifications of the server to the app
+		let (to_frontend, from_server) = channel();
+		let notification_sink = event_sink.clone();
+		thread::spawn(move || {
+			for notification in from_server {
+				if notification_sink
+					.submit_command(cmds::SERVER_NOTIFICATION, notification, Target::Auto)
+					.is_err()
+				{
+					break;
+				}
+			}
+		});
+
+		let outputs = settings::make_outputs(settings, &midi_ports);
+		let playhead = data.editors.sheet_editor.playhead.clone();
+		let server = server::launch(outputs, data.meters.clone(), playhead, to_frontend.clone());
+
+		let mut delegate = Delegate {
+			server,
+			to_frontend,
 			after_save: None,
-			midi_ports: vec![],
+			export_pending: false,
+			midi_ports,
+			recovery_pending: state::recovery::start_session(),
+			startup_error: None,
+			autosave_failed: false,
+			play_origin: 0.0,
+			event_sink,
+			input: midi_input::Input::new(input_ports),
+		};
+		if delegate.connect_midi_input(data).is_err() {
+			data.editors.settings.midi_input.enabled = false;
+		}
+		delegate.sync_server(data);
+		Ok(delegate)
+	}
+
+	// sends the parts of the project the server needs to know about
+	fn sync_server(&self, data: &State) {
+		let sheet_editor = &data.editors.sheet_editor;
+		self.server.send(server::Event::SetTempo(sheet_editor.tempo));
+		self.server.send(server::Event::SetLoop(sheet_editor.loop_region.range()));
+		self.server.send(server::Event::SetMetronome(sheet_editor.metronome));
+		self.send_layout(data);
+		self.send_patch(data);
+		if sheet_editor.playing {
+			self.server
+				.send(server::Event::PlayStart(sheet_editor.sheet.borrow().clone(), sheet_editor.cursor));
+		}
+	}
+
+	pub fn show_on_start(&mut self, message: String) {
+		self.startup_error = Some(message);
+	}
+
+	// the overlay shows one modal at a time, the recovery prompt comes first
+	fn show_startup_error(&mut self, ctx: &mut DelegateCtx, data: &State) {
+		if let Some(message) = self.startup_error.take() {
+			self.show_error(ctx, data, message);
 		}
 	}
+
+	fn send_layout(&self, data: &State) {
+		let layout = data.editors.sheet_editor.layout.borrow().clone();
+		self.server.send(server::Event::SetLayout(layout));
+	}
 }
 
 mod fileops;
 mod history;
 mod layout;
+mod midi_input;
+mod recovery;
 mod settings;
+mod synth;
 
 impl AppDelegate<State> for Delegate {
 	fn command(&mut self, ctx: &mut DelegateCtx, _target: Target, cmd: &Command, data: &mut State, _env: &Env) -> bool {
 		let mut project_changed = false;
+		let mut redraw = false; // for the edits that don't replace the whole project
 
 		let mut propagate = true;
 
@@ -38,62 +137,107 @@ impl AppDelegate<State> for Delegate {
 		propagate &= self.handle_fileops(ctx, cmd, data, &mut project_changed);
 		propagate &= self.handle_settings(ctx, cmd, data, &mut project_changed);
 		propagate &= self.handle_layout(ctx, cmd, data, &mut project_changed);
+		propagate &= self.handle_recovery(ctx, cmd, data, &mut project_changed);
+		propagate &= self.handle_synth(ctx, cmd, data, &mut project_changed);
+		propagate &= self.handle_midi_input(ctx, cmd, data, &mut project_changed);
 
 		propagate &= match cmd {
 			_ if cmd.is(cmds::TEMPO_CHANGED) => {
 				let tempo = *cmd.get_unchecked(cmds::TEMPO_CHANGED);
-				self.to_server.send(server::Event::SetTempo(tempo)).unwrap();
+				self.server.send(server::Event::SetTempo(tempo));
+				// for the delay synced to the tempo
+				self.send_patch(data);
+				true
+			}
+			_ if cmd.is(cmds::LOOP_CHANGED) => {
+				let loop_region = cmd.get_unchecked(cmds::LOOP_CHANGED);
+				self.server.send(server::Event::SetLoop(loop_region.range()));
 				true
 			}
+			_ if cmd.is(cmds::METRONOME_CHANGED) => {
+				let metronome = *cmd.get_unchecked(cmds::METRONOME_CHANGED);
+				self.server.send(server::Event::SetMetronome(metronome));
+				true
+			}
+			_ if cmd.is(cmds::OPEN_HEX_KEYBOARD) => {
+				let new_win = WindowDesc::new(ui::build_hex_keyboard)
+					.title(LocalizedString::new("Hex keyboard"))
+					.window_size((800.0, 400.0));
+				ctx.new_window(new_win);
+				false
+			}
 			_ if cmd.is(cmds::PLAY_START) => {
 				data.editors.sheet_editor.playing = true;
-				self.to_server
-					.send(server::Event::PlayStart(
-						data.editors.sheet_editor.sheet.borrow().clone(),
-						data.editors.sheet_editor.cursor,
-					))
-					.unwrap();
+				self.play_origin = data.editors.sheet_editor.cursor;
+				self.send_patch(data);
+				self.server.send(server::Event::PlayStart(
+					data.editors.sheet_editor.sheet.borrow().clone(),
+					data.editors.sheet_editor.cursor,
+				));
 				true
 			}
 			_ if cmd.is(cmds::PLAY_STOP) => {
+				// before the cursor goes back to the start
+				let stopped_at = Self::position(data);
+				self.stop_midi_recording(ctx, data, stopped_at);
+				ctx.submit_command(cmds::RECORDING_STOPPED.with(stopped_at).to(Target::Global));
 				data.editors.sheet_editor.playing = false;
-				self.to_server.send(server::Event::PlayStop).unwrap();
+				if data.editors.settings.return_to_start {
+					data.editors.sheet_editor.cursor = self.play_origin;
+				}
+				self.server.send(server::Event::PlayStop);
 				true
 			}
 			_ if cmd.is(cmds::ICP) => {
 				let icp_event = *cmd.get_unchecked(cmds::ICP);
-				self.to_server.send(server::Event::ICP(icp_event)).unwrap();
+				self.server.send(server::Event::ICP(icp_event));
 				false
 			}
 
 			_ if cmd.is(cmds::SHEET_CHANGED) => {
-				self.to_server
-					.send(server::Event::SheetChanged(data.editors.sheet_editor.sheet.borrow().clone()))
-					.unwrap();
-				project_changed = true;
+				self.server
+					.send(server::Event::SheetChanged(data.editors.sheet_editor.sheet.borrow().clone()));
+				redraw = true;
 				true
 			}
 			_ if cmd.is(cmds::LAYOUT_CHANGED) => {
-				project_changed = true;
+				// the partials of the patch depend on the layout
+				self.send_layout(data);
+				self.send_patch(data);
+				redraw = true;
 				true
 			}
 			_ => true,
 		};
 		if project_changed {
+			// the opened project may come with another patch, or another layout for its partials and the metronome
+			self.send_patch(data);
+			self.send_layout(data);
+		}
+		if project_changed || redraw {
 			ctx.submit_command(cmds::REDRAW.to(Target::Global));
 		}
 		propagate
 	}
 
-	fn window_added(&mut self, id: WindowId, data: &mut State, _env: &Env, _ctx: &mut DelegateCtx) {
+	fn window_added(&mut self, id: WindowId, data: &mut State, _env: &Env, ctx: &mut DelegateCtx) {
 		if data.main_window.is_none() {
 			data.main_window = Some(Rc::new(id));
+			if self.recovery_pending {
+				self.recovery_pending = false;
+				ctx.submit_command(widget::overlay::SHOW_MIDDLE.with(ui::modal::recover::build()).to(id));
+			} else {
+				self.show_startup_error(ctx, data);
+			}
 		}
 	}
 
 	fn window_removed(&mut self, id: WindowId, data: &mut State, _env: &Env, _ctx: &mut DelegateCtx) {
 		let main_id = (data.main_window.clone()).unwrap();
 		if id == *main_id {
+			// silences the outputs, the process exits before they would be dropped
+			self.server.shutdown().ok();
+			state::recovery::end_session();
 			std::process::exit(0);
 		}
 	}
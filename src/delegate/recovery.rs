@@ -0,0 +1,68 @@
+use super::Delegate;
+use crate::commands as cmds;
+use crate::state::{self, recovery, State};
+use druid::{Command, DelegateCtx};
+use std::rc::Rc;
+
+impl Delegate {
+	pub fn handle_recovery(
+		&mut self,
+		ctx: &mut DelegateCtx,
+		cmd: &Command,
+		data: &mut State,
+		project_changed: &mut bool,
+	) -> bool {
+		match cmd {
+			_ if cmd.is(cmds::AUTOSAVE) => {
+				if data.up_to_date {
+					recovery::clear();
+				} else {
+					match recovery::save(&make_backup(data)) {
+						Ok(()) => self.autosave_failed = false,
+						Err(err) if !self.autosave_failed => {
+							self.autosave_failed = true;
+							self.show_error(ctx, data, format!("Failed to autosave the project: {}", err));
+						}
+						Err(_) => {}
+					}
+				}
+				false
+			}
+			_ if cmd.is(cmds::RECOVERY_RESTORE) => {
+				match recovery::load() {
+					Ok(backup) => {
+						data.history.borrow_mut().save(backup.project.clone());
+						backup.project.open(&mut data.editors);
+						data.save_path = backup.save_path.map(Rc::new);
+						data.up_to_date = false;
+						*project_changed = true;
+						// the project is now backed up by this session
+						if recovery::save(&make_backup(data)).is_ok() {
+							recovery::discard();
+						}
+						self.show_startup_error(ctx, data);
+					}
+					Err(err) => {
+						// the backup is left where it is, it may still be opened by hand
+						let path = recovery::recovered_path().map(|path| path.display().to_string()).unwrap_or_default();
+						self.show_error(ctx, data, format!("The backup failed to open: {}. It was kept at {}", err, path));
+					}
+				}
+				false
+			}
+			_ if cmd.is(cmds::RECOVERY_DISCARD) => {
+				recovery::discard();
+				self.show_startup_error(ctx, data);
+				false
+			}
+			_ => true,
+		}
+	}
+}
+
+fn make_backup(data: &State) -> recovery::Backup {
+	recovery::Backup {
+		save_path: data.save_path.as_ref().map(|path| (**path).clone()),
+		project: state::Project::from_editors(&data.editors),
+	}
+}
@@ -1,12 +1,86 @@
 use super::Delegate;
 use crate::commands as cmds;
 use crate::server;
-use crate::state::State;
+use crate::state::{self, editors::settings, State};
 use crate::ui;
-use crate::widget::common::*;
-use druid::{Command, DelegateCtx, LocalizedString, WindowDesc};
+use crate::widget::{self, common::*};
+use druid::{Command, DelegateCtx, LocalizedString, WidgetId, WindowDesc};
+use std::error::Error;
+
+pub fn list_midi_ports() -> Result<(Vec<midir::MidiOutputPort>, Vec<String>), Box<dyn Error>> {
+	let midi = midir::MidiOutput::new("mpe backend")?;
+	let ports = midi.ports();
+	let port_names = ports.iter().map(|p| midi.port_name(p)).collect::<Result<_, _>>()?;
+	Ok((ports, port_names))
+}
+
+/// Gives the names listed by the system to a choice and to its selector, the list may have changed since it was shown
+pub fn set_choices(ctx: &mut DelegateCtx, choice: &mut settings::Choice, names: Vec<String>, target: WidgetId) {
+	choice.set_names(names);
+	ctx.submit_command(index_selector::SET_CHOICES.with((*choice.names).clone()).to(target));
+}
+
+pub fn make_outputs(settings: &settings::State, midi_ports: &[midir::MidiOutputPort]) -> server::Outputs {
+	server::Outputs {
+		synth: settings.synth,
+		audio: server::AudioConfig {
+			device: settings.audio_device(),
+			sample_rate: settings.audio.sample_rate(),
+			buffer_size: settings.audio.buffer_size(),
+		},
+		mpe: if settings.mpe.enabled {
+			let mpe = &settings.mpe;
+			midi_ports.get(mpe.port.index).map(|port| server::midi::MidiConfig {
+				port: port.clone(),
+				mpe: mpe.mode == settings::MidiMode::Mpe,
+				channels: (mpe.first_channel.max(1).min(16) - 1)..=(mpe.last_channel.max(1).min(16) - 1),
+				bend_range: mpe.bend_range,
+				stealing: mpe.stealing,
+			})
+		} else {
+			None
+		},
+		osc: if settings.osc.enabled {
+			Some(server::OscConfig {
+				target: settings.osc.target.clone(),
+				note_on: settings.osc.note_on.clone(),
+				note_off: settings.osc.note_off.clone(),
+				change_freq: settings.osc.change_freq.clone(),
+			})
+		} else {
+			None
+		},
+	}
+}
 
 impl Delegate {
+	fn relaunch_server(&mut self, ctx: &mut DelegateCtx, data: &State) {
+		if self.server.shutdown().is_err() {
+			self.show_error(ctx, data, "The server crashed".into());
+		}
+		let outputs = make_outputs(&data.editors.settings, &self.midi_ports);
+		let playhead = data.editors.sheet_editor.playhead.clone();
+		self.server = server::launch(outputs, data.meters.clone(), playhead, self.to_frontend.clone());
+		self.sync_server(data);
+	}
+
+	pub fn show_error(&self, ctx: &mut DelegateCtx, data: &State, message: String) {
+		if let Some(main_window) = &data.main_window {
+			ctx.submit_command(
+				widget::overlay::SHOW_MIDDLE
+					.with(ui::modal::error::build(message))
+					.to(**main_window),
+			);
+		}
+	}
+
+	/// Shows the message in the toolbar without interrupting the user, for what doesn't need an answer
+	pub fn show_status(&self, ctx: &mut DelegateCtx, data: &State, message: String) {
+		if let Some(main_window) = &data.main_window {
+			ctx.submit_command(cmds::SHOW_STATUS.with(message).to(**main_window));
+		}
+	}
+
 	pub fn handle_settings(
 		&mut self,
 		ctx: &mut DelegateCtx,
@@ -18,37 +92,75 @@ impl Delegate {
 			_ if cmd.is(cmds::OPEN_SETTINGS) => {
 				let new_win = WindowDesc::new(ui::build_settings)
 					.title(LocalizedString::new("Settings"))
-					.window_size((700.0, 200.0));
+					.window_size((900.0, 500.0));
 				ctx.new_window(new_win);
 				false
 			}
 			_ if cmd.is(cmds::SETTINGS_APPLY) => {
 				data.editors.apply_settings(ctx);
+				if let Err(err) = self.connect_midi_input(data) {
+					data.editors.settings.midi_input.enabled = false;
+					self.show_error(ctx, data, format!("Couldn't connect to the MIDI input: {}", err));
+				}
+				if let Err(err) = state::config::save(&data.editors.settings) {
+					self.show_error(ctx, data, format!("Failed to save the settings: {}", err));
+				}
+				false
+			}
+			_ if cmd.is(cmds::BACKEND_SET_OUTPUTS) => {
+				self.relaunch_server(ctx, data);
 				false
 			}
-			_ if cmd.is(cmds::BACKEND_SET_AUDIO) => {
-				self.to_server.send(server::Event::Shutdown).unwrap();
-				std::thread::sleep(std::time::Duration::from_secs(1)); // not great, should handle server thread failures instead
-				self.to_server = server::audio::launch().unwrap();
+			_ if cmd.is(cmds::SERVER_NOTIFICATION) => {
+				match cmd.get_unchecked(cmds::SERVER_NOTIFICATION) {
+					server::Notification::OutputFailed(output, err) => {
+						let settings = &mut data.editors.settings;
+						match output {
+							server::Output::Synth => settings.synth = false,
+							server::Output::Mpe => settings.mpe.enabled = false,
+							server::Output::Osc => settings.osc.enabled = false,
+						}
+						// fall back to the synth, unless it is the one failing
+						if *output != server::Output::Synth && !settings.mpe.enabled && !settings.osc.enabled {
+							settings.synth = true;
+						}
+						self.show_error(ctx, data, format!("The {} output stopped: {}", output, err));
+						self.relaunch_server(ctx, data);
+					}
+					server::Notification::Error(err) => {
+						self.show_error(ctx, data, format!("The server stopped: {}", err));
+					}
+					// these come during playback, a modal would get in the way
+					server::Notification::Warning(message) | server::Notification::Info(message) => {
+						self.show_status(ctx, data, message.clone());
+					}
+				}
 				false
 			}
-			_ if cmd.is(cmds::BACKEND_SET_MPE) => {
-				self.to_server.send(server::Event::Shutdown).unwrap();
-				std::thread::sleep(std::time::Duration::from_secs(1));
-				let port = *cmd.get_unchecked(cmds::BACKEND_SET_MPE);
-				self.to_server = server::midi::launch(self.midi_ports[port].clone()).unwrap();
+			_ if cmd.is(cmds::BACKEND_AUDIO_REQUEST_DEVICES) => {
+				let target = *cmd.get_unchecked(cmds::BACKEND_AUDIO_REQUEST_DEVICES);
+				let device_names = match server::audio::list_devices() {
+					Ok(device_names) => device_names,
+					Err(err) => {
+						self.show_error(ctx, data, format!("Couldn't list the audio devices: {}", err));
+						return false;
+					}
+				};
+				let choices = std::iter::once(settings::DEFAULT_DEVICE.to_string()).chain(device_names).collect();
+				set_choices(ctx, &mut data.editors.settings.audio.device, choices, target);
 				false
 			}
 			_ if cmd.is(cmds::BACKEND_MPE_REQUEST_PORTS) => {
 				let target = *cmd.get_unchecked(cmds::BACKEND_MPE_REQUEST_PORTS);
-				let midi = midir::MidiOutput::new("mpe backend").expect("couldn't open midi output");
-				let ports = midi.ports();
-				let port_names = ports
-					.iter()
-					.map(|p| midi.port_name(p).expect("couldn't get midi port name"))
-					.collect();
+				let (ports, port_names) = match list_midi_ports() {
+					Ok(ports) => ports,
+					Err(err) => {
+						self.show_error(ctx, data, format!("Couldn't list the MIDI ports: {}", err));
+						return false;
+					}
+				};
 				self.midi_ports = ports;
-				ctx.submit_command(index_selector::SET_CHOICES.with(port_names).to(target));
+				set_choices(ctx, &mut data.editors.settings.mpe.port, port_names, target);
 				false
 			}
 			_ => true,
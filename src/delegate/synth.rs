@@ -0,0 +1,49 @@
+use super::Delegate;
+use crate::commands as cmds;
+use crate::data::synth::Patch;
+use crate::server;
+use crate::state::State;
+use crate::ui;
+use druid::{Command, DelegateCtx, LocalizedString, WindowDesc};
+use std::sync::Arc;
+
+/// The patch with its partials fitted to the layout at the cursor, and its delay to the tempo
+pub fn resolved_patch(data: &State) -> Patch {
+	let sheet_editor = &data.editors.sheet_editor;
+	let layout = sheet_editor.layout.borrow();
+	let period = match &layout.get_marker_at(sheet_editor.cursor, None).1.freq {
+		Some(freq_pattern) => freq_pattern.period(),
+		None => 2.0,
+	};
+	data.editors.patch.resolve(period, sheet_editor.tempo)
+}
+
+impl Delegate {
+	pub fn send_patch(&self, data: &State) {
+		self.server.send(server::Event::SetPatch(Arc::new(resolved_patch(data))));
+	}
+
+	pub fn handle_synth(
+		&mut self,
+		ctx: &mut DelegateCtx,
+		cmd: &Command,
+		data: &mut State,
+		_project_changed: &mut bool,
+	) -> bool {
+		match cmd {
+			_ if cmd.is(cmds::OPEN_SYNTH_EDITOR) => {
+				let new_win = WindowDesc::new(ui::build_synth_editor)
+					.title(LocalizedString::new("Synth"))
+					.window_size((1000.0, 550.0));
+				ctx.new_window(new_win);
+				false
+			}
+			_ if cmd.is(cmds::SYNTH_APPLY) => {
+				self.send_patch(data);
+				ctx.submit_command(cmds::HISTORY_SAVE);
+				false
+			}
+			_ => true,
+		}
+	}
+}
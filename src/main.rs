@@ -1,4 +1,5 @@
-use druid::{AppLauncher, LocalizedString, Size, WindowDesc};
+use druid::{AppLauncher, LocalizedString, Size, Target, WindowDesc};
+use std::thread;
 
 #[macro_use]
 mod util;
@@ -15,21 +16,29 @@ mod delegate;
 use delegate::Delegate;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-	let state = State::new();
-
-	let to_server = server::audio::launch()?;
-
-	to_server
-		.send(server::Event::SetTempo(state.editors.sheet_editor.tempo))
-		.unwrap();
+	let mut state = State::new();
 
 	let main_window = WindowDesc::new(ui::build)
 		.title(LocalizedString::new("Harmoxen v0.2.0"))
 		.window_size(Size::new(800.0, 500.0));
 
-	let delegate = Delegate::new(to_server);
+	let launcher = AppLauncher::with_window(main_window);
+
+	let (settings, config_error) = state::config::load();
+	let mut delegate = Delegate::new(settings, &mut state, launcher.get_external_handle())?;
+	if let Some(message) = config_error {
+		delegate.show_on_start(message);
+	}
+
+	let event_sink = launcher.get_external_handle();
+	thread::spawn(move || loop {
+		thread::sleep(state::recovery::AUTOSAVE_INTERVAL);
+		if event_sink.submit_command(commands::AUTOSAVE, (), Target::Auto).is_err() {
+			break;
+		}
+	});
 
-	AppLauncher::with_window(main_window)
+	launcher
 		.delegate(delegate)
 		.configure_env(theme::apply)
 		.launch(state)
@@ -1,153 +1,258 @@
 #![allow(unused)]
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::sync::mpsc::*;
-use std::thread;
-
-use crate::data::{icp, sheet::*};
-use crate::util::*;
-
-pub fn launch() -> Result<Sender<Event>, Box<dyn Error>> {
-	let (to_server, from_frontend) = channel();
-	let (to_backend, from_server) = channel();
-	thread::spawn(move || {
-		let mut stream = run(from_server);
-		while let Ok(event) = from_frontend.recv() {
-			if let Event::Shutdown = event {
-				stream.pause();
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{bin::Garbage, AudioConfig, Backend, Bin, Engine, Event, Meters, Notification, Output, Playhead};
+use crate::data::{icp, layout::Click, synth::Patch};
+
+// many updates worth of events, the callback drops the ones that don't fit rather than wait
+const EXTERNAL_CAPACITY: usize = 4096;
+
+// an event for the external outputs, and when the synth plays it through the device
+type Timed = (Instant, icp::Event);
+
+/// The synth playing through the audio device, which clocks the transport
+pub struct Stream {
+	stream: Box<dyn StreamTrait>,
+	to_stream: Sender<Event>,
+	from_stream: Receiver<Timed>,
+	pending: VecDeque<Timed>,
+	from_bin: Receiver<Garbage>,
+	loader: synth::resources::Loader,
+	to_frontend: Sender<Notification>,
+}
+
+impl Stream {
+	/// With `external`, the events of the transport are also handed back for the external outputs
+	pub fn new(
+		audio: &AudioConfig,
+		external: bool,
+		meters: Arc<Meters>,
+		playhead: Arc<Playhead>,
+		to_frontend: Sender<Notification>,
+	) -> Result<Stream, Box<dyn Error>> {
+		let (to_stream, from_server) = channel();
+		// bounded, so that the buffer is allocated here and not in the callback
+		let (to_server, from_stream) = sync_channel(EXTERNAL_CAPACITY);
+		let forward = if external {
+			Some(Forward {
+				to_server,
+				heard_at: Instant::now(),
+			})
+		} else {
+			None
+		};
+		let (bin, from_bin) = Bin::new();
+		let stream = build(from_server, forward, bin, audio, meters, playhead, to_frontend.clone())?;
+		Ok(Stream {
+			stream,
+			to_stream,
+			from_stream,
+			pending: VecDeque::with_capacity(EXTERNAL_CAPACITY),
+			from_bin,
+			loader: synth::resources::Loader::default(),
+			to_frontend,
+		})
+	}
+
+	/// Frees what the callback replaced
+	pub fn empty_bin(&self) {
+		for _ in self.from_bin.try_iter() {}
+	}
+
+	/// Gives the events of the external outputs that are heard by `now`, in the order of the transport
+	pub fn for_due_events(&mut self, now: Instant, mut f: impl FnMut(icp::Event)) {
+		self.pending.extend(self.from_stream.try_iter());
+		while let Some(&(heard_at, event)) = self.pending.front() {
+			if heard_at > now {
 				break;
 			}
-			to_backend
-				.send(event)
-				.map_err(|e| format!("connection to audio backend closed unexpectedly: {}", e))
-				.unwrap();
+			self.pending.pop_front();
+			f(event);
+		}
+	}
+
+	pub fn send(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+		// files are read here, the audio thread must not wait on them
+		if let Event::SetPatch(patch) = &event {
+			let to_frontend = &self.to_frontend;
+			let resources = self.loader.update(patch, |err| {
+				to_frontend.send(Notification::Warning(err)).ok();
+			});
+			if let Some(resources) = resources {
+				self.to_stream.send(Event::SetResources(resources)).ok();
+			}
 		}
-	});
-	Ok(to_server)
+		self.to_stream
+			.send(event)
+			.map_err(|e| format!("connection to audio backend closed unexpectedly: {}", e))?;
+		Ok(())
+	}
+
+	pub fn stop(self) -> Result<(), Box<dyn Error>> {
+		self.stream.pause()?;
+		Ok(())
+	}
 }
 
-use super::Event;
+// hands the events of the transport to the server thread, which sends them to the external outputs when they are heard
+struct Forward {
+	to_server: SyncSender<Timed>,
+	heard_at: Instant,
+}
 
-pub fn run(mut receiver: Receiver<Event>) -> Box<dyn StreamTrait> {
-	let host = cpal::default_host();
+impl Backend for Forward {
+	fn process(&mut self, offset: f64, event: icp::Event) -> Result<(), Box<dyn Error>> {
+		// the server may be shutting down, it silences the external outputs itself
+		self.to_server
+			.try_send((self.heard_at + Duration::from_secs_f64(offset.max(0.0)), event))
+			.ok();
+		Ok(())
+	}
 
-	let device = host.default_output_device().expect("failed to find a default output device");
-	let supported_config = device.default_output_config().expect("failed to get default output config");
-	let config = supported_config.config();
+	fn start_update(&mut self, heard_at: Instant) {
+		self.heard_at = heard_at;
+	}
+}
 
-	let nb_channels = config.channels as usize;
+fn build(
+	receiver: Receiver<Event>,
+	forward: Option<Forward>,
+	bin: Bin,
+	audio: &AudioConfig,
+	meters: Arc<Meters>,
+	playhead: Arc<Playhead>,
+	to_frontend: Sender<Notification>,
+) -> Result<Box<dyn StreamTrait>, Box<dyn Error>> {
+	let host = cpal::default_host();
+
+	let device = match &audio.device {
+		Some(name) => host
+			.output_devices()?
+			.find(|device| device.name().ok().as_ref() == Some(name))
+			.ok_or_else(|| format!("the audio device \"{}\" is not available", name))?,
+		None => host.default_output_device().ok_or("failed to find a default output device")?,
+	};
+	let supported_config = match audio.sample_rate {
+		Some(rate) => device
+			.supported_output_configs()?
+			.find(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+			.map(|range| range.with_sample_rate(cpal::SampleRate(rate)))
+			.ok_or_else(|| format!("the audio device doesn't support a sample rate of {} Hz", rate))?,
+		None => device.default_output_config()?,
+	};
+	let mut config = supported_config.config();
+	if let Some(size) = audio.buffer_size {
+		// sizes outside of what the device supports are clamped
+		config.buffer_size = cpal::BufferSize::Fixed(match supported_config.buffer_size() {
+			cpal::SupportedBufferSize::Range { min, max } => size.max(*min).min(*max),
+			cpal::SupportedBufferSize::Unknown => size,
+		});
+	}
 
 	let period = 1.0 / f64::from(config.sample_rate.0);
 
-	let mut engine = Engine::new(period);
+	let mut synth = Synth::new(period, config.channels as usize);
+	synth.set_meters(meters);
+	synth.set_bin(bin.clone());
+	let mut backends: Vec<(Output, Box<dyn Backend>)> = vec![(Output::Synth, Box::new(synth))];
+	if let Some(forward) = forward {
+		// the external outputs report their own failures from the server thread
+		backends.push((Output::Synth, Box::new(forward)));
+	}
+	let mut engine = Engine::new(backends, to_frontend.clone());
+	engine.transport.set_bin(bin);
+	engine.set_playhead(playhead);
 
 	let stream = match supported_config.sample_format() {
-		cpal::SampleFormat::F32 => build_stream::<f32>(device, receiver, engine, config),
-		cpal::SampleFormat::I16 => build_stream::<i16>(device, receiver, engine, config),
-		cpal::SampleFormat::U16 => build_stream::<u16>(device, receiver, engine, config),
+		cpal::SampleFormat::F32 => build_stream::<f32>(device, receiver, engine, config, to_frontend)?,
+		cpal::SampleFormat::I16 => build_stream::<i16>(device, receiver, engine, config, to_frontend)?,
+		cpal::SampleFormat::U16 => build_stream::<u16>(device, receiver, engine, config, to_frontend)?,
 	};
 
-	stream.play().expect("failed to play audio stream");
+	stream.play()?;
 
-	stream
+	Ok(stream)
 }
 
 fn build_stream<T>(
 	device: cpal::Device,
-	mut receiver: Receiver<Event>,
+	receiver: Receiver<Event>,
 	mut engine: Engine,
 	config: cpal::StreamConfig,
-) -> Box<dyn StreamTrait>
+	to_frontend: Sender<Notification>,
+) -> Result<Box<dyn StreamTrait>, Box<dyn Error>>
 where
 	T: cpal::Sample,
 {
 	let nb_channels = config.channels as usize;
-	Box::new(
-		device
-			.build_output_stream::<T, _, _>(
-				&config,
-				move |data, _| {
-					while let Ok(event) = receiver.try_recv() {
-						engine.process_event(event);
-					}
-
-					engine.update(data.len() / nb_channels as usize);
-					for frame in data.chunks_mut(nb_channels) {
-						let value = cpal::Sample::from::<f32>(&(engine.next_sample() as f32));
-						for sample in frame.iter_mut() {
-							*sample = value;
-						}
-					}
-				},
-				|err| println!("an error occured on stream: {}", err),
-			)
-			.expect("failed to build the output stream"),
-	)
+	let period = 1.0 / f64::from(config.sample_rate.0);
+	let mut values = vec![0.0; nb_channels];
+	Ok(Box::new(device.build_output_stream::<T, _, _>(
+		&config,
+		move |data, info: &cpal::OutputCallbackInfo| {
+			// the events of this update are heard once the buffer goes through the device
+			let timestamp = info.timestamp();
+			let latency = timestamp.playback.duration_since(&timestamp.callback).unwrap_or_default();
+			engine.start_update(Instant::now() + latency);
+			while let Ok(event) = receiver.try_recv() {
+				engine.process_event(event);
+			}
+
+			engine.update((data.len() / nb_channels) as f64 * period);
+			for frame in data.chunks_mut(nb_channels) {
+				engine.next_frame(&mut values);
+				for (sample, value) in frame.iter_mut().zip(&values) {
+					*sample = cpal::Sample::from::<f32>(&(*value as f32));
+				}
+			}
+		},
+		move |err| {
+			to_frontend
+				.send(Notification::OutputFailed(Output::Synth, err.to_string()))
+				.ok();
+		},
+	)?))
+}
+
+/// The names of the audio devices of the system, which on Linux includes the ALSA devices along with the sound servers
+pub fn list_devices() -> Result<Vec<String>, Box<dyn Error>> {
+	let host = cpal::default_host();
+	let names = host.output_devices()?.map(|device| device.name()).collect::<Result<_, _>>()?;
+	Ok(names)
 }
 
+mod render;
+pub use render::render;
 mod synth;
+pub use synth::resources::Resources;
+pub use synth::Voice;
 use synth::Synth;
 
-struct Engine {
-	sheet: Sheet,
-	cursor: f64,
-	active: bool,
-	synth: Synth,
-	tempo: f64,
-}
+impl Backend for Synth {
+	fn process(&mut self, offset: f64, event: icp::Event) -> Result<(), Box<dyn Error>> {
+		self.schedule(offset, event);
+		Ok(())
+	}
 
-impl Engine {
-	pub fn new(period: f64) -> Engine {
-		Engine {
-			sheet: Sheet::default(),
-			cursor: 0.0,
-			active: false,
-			synth: Synth::new(period),
-			tempo: 140.0,
-		}
+	fn click(&mut self, offset: f64, click: Click) {
+		self.schedule_click(offset, click);
 	}
 
-	pub fn process_event(&mut self, event: Event) {
-		match event {
-			Event::SetTempo(tempo) => {
-				self.tempo = tempo;
-			}
-			Event::PlayStart(sheet, cursor) => {
-				self.cursor = cursor;
-				self.synth.process_events(&sheet.get_events_at_time(cursor));
-				self.sheet = sheet;
-				self.active = true;
-			}
-			Event::PlayStop => {
-				self.active = false;
-				self.synth.process_events(&[icp::Event::NoteStopAll]);
-			}
-			Event::SheetChanged(sheet) => {
-				self.sheet = sheet;
-			}
-			Event::ICP(icp) => {
-				self.synth.process_events(&[icp]);
-			}
-			_ => {}
-		}
+	fn next_frame(&mut self, frame: &mut [f64]) {
+		Synth::next_frame(self, frame);
 	}
 
-	pub fn update(&mut self, samples: usize) {
-		if self.active {
-			let length = samples as f64 * self.synth.period * (self.tempo / 60.0);
-			let range = Range(self.cursor, self.cursor + length);
-			self.cursor += length;
-			let mut events = self.sheet.get_events(range);
-			if self.cursor > self.sheet.get_size() {
-				self.cursor %= self.sheet.get_size();
-				events.extend(self.sheet.get_events(Range(0.0, self.cursor)));
-			}
-			self.synth.process_events(&events);
-		}
+	fn set_patch(&mut self, patch: &Arc<Patch>) {
+		Synth::set_patch(self, patch.clone());
 	}
 
-	pub fn next_sample(&mut self) -> f64 {
-		self.synth.next_sample()
+	fn set_resources(&mut self, resources: &Arc<Resources>) {
+		Synth::set_resources(self, resources.clone());
 	}
 }
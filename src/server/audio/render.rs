@@ -0,0 +1,73 @@
+//! Offline rendering of a sheet through the synth
+
+use super::synth::{resources::Loader, Synth};
+use crate::data::{sheet::Sheet, synth::Patch};
+use crate::server::{Backend, Engine, Event, Output};
+use std::error::Error;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+
+const SAMPLE_RATE: u32 = 48000;
+const NB_CHANNELS: usize = 2;
+const BLOCK_SIZE: usize = 256;
+// after the release of the last notes, the effects ring until the output stays below this level
+const SILENCE: f64 = 0.0001; // about -80 dB
+const SILENCE_TIME: f64 = 0.5; // in seconds, so that a quiet moment of the reverb isn't taken for its end
+const MAX_TAIL: f64 = 30.0; // in seconds, for a delay that feeds back forever
+
+/// Renders the whole sheet once to a stereo WAV file
+pub fn render(sheet: Sheet, tempo: f64, patch: Patch, path: &Path) -> Result<(), Box<dyn Error>> {
+	let period = 1.0 / f64::from(SAMPLE_RATE);
+
+	let mut synth = Synth::new(period, NB_CHANNELS);
+	let mut errors = vec![];
+	if let Some(resources) = Loader::default().update(&patch, |err| errors.push(err)) {
+		synth.set_resources(resources);
+	}
+	if let Some(err) = errors.into_iter().next() {
+		return Err(err.into());
+	}
+	synth.set_patch(Arc::new(patch.clone()));
+
+	// the synth can't fail here, so its notifications are dropped
+	let (to_frontend, _) = channel();
+	let backends: Vec<(Output, Box<dyn Backend>)> = vec![(Output::Synth, Box::new(synth))];
+	let mut engine = Engine::new(backends, to_frontend);
+	engine.transport.looping = false;
+	let duration = sheet.get_size() * 60.0 / tempo + patch.envelope.release;
+	engine.process_event(Event::SetTempo(tempo));
+	engine.process_event(Event::PlayStart(sheet, 0.0));
+
+	let spec = hound::WavSpec {
+		channels: NB_CHANNELS as u16,
+		sample_rate: SAMPLE_RATE,
+		bits_per_sample: 32,
+		sample_format: hound::SampleFormat::Float,
+	};
+	let mut writer = hound::WavWriter::create(path, spec)?;
+	let mut frame = [0.0; NB_CHANNELS];
+	let min_frames = (duration * f64::from(SAMPLE_RATE)) as usize;
+	let max_frames = ((duration + MAX_TAIL) * f64::from(SAMPLE_RATE)) as usize;
+	let silence_frames = (SILENCE_TIME * f64::from(SAMPLE_RATE)) as usize;
+	let mut silent = 0; // frames in a row below the threshold
+	for i in 0..max_frames {
+		if i >= min_frames && silent >= silence_frames {
+			break;
+		}
+		if i % BLOCK_SIZE == 0 {
+			engine.update(BLOCK_SIZE as f64 * period);
+		}
+		engine.next_frame(&mut frame);
+		if frame.iter().all(|x| x.abs() < SILENCE) {
+			silent += 1;
+		} else {
+			silent = 0;
+		}
+		for &x in &frame {
+			writer.write_sample(x as f32)?;
+		}
+	}
+	writer.finalize()?;
+	Ok(())
+}
@@ -1,101 +1,463 @@
 use crate::data::icp::*;
+use crate::data::layout::Click;
+use crate::data::synth::{Filter, FilterKind, Patch, Spectrum, Stealing, Waveform};
+use crate::server::{bin::Garbage, meters::MAX_CHANNELS, Bin, Meters};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
 
+mod additive;
+use additive::Additive;
 mod adsr;
 use adsr::ADSR;
+mod click;
+use click::ClickOsc;
+mod delay;
+use delay::DelayLine;
+mod fm;
+use fm::Fm;
 mod limiter;
 use limiter::Limiter;
 mod osc;
+mod reverb;
+use reverb::Freeverb;
+pub mod resources;
+use resources::Resources;
+mod sampler;
+use sampler::Sampler;
 mod svf;
 use osc::Osc;
+mod wavetable;
+use wavetable::WavetableOsc;
 
-pub const ATTACK: f64 = 0.2;
-pub const DECAY: f64 = 0.1;
-pub const SUSTAIN: f64 = 0.8;
-pub const RELEASE: f64 = 0.1;
+enum Source {
+	Osc(Osc),
+	Additive(Additive),
+	Sampler(Sampler),
+	Wavetable(WavetableOsc),
+	Fm(Fm),
+	Silence,
+}
+
+impl Source {
+	fn next(&mut self, delta: f64) -> f64 {
+		match self {
+			Source::Osc(osc) => osc.next(delta),
+			Source::Additive(additive) => additive.next(delta),
+			Source::Sampler(sampler) => sampler.next(delta),
+			Source::Wavetable(wavetable) => wavetable.next(delta),
+			Source::Fm(fm) => fm.next(delta),
+			Source::Silence => 0.0,
+		}
+	}
+}
+
+const FADE_TIME: f64 = 0.005; // in seconds, for voices cut before their end
 
-struct Voice {
-	osc: Osc,
+pub struct Voice {
+	source: Source,
 	note: Note,
 	adsr: ADSR,
+	gains: [f64; MAX_CHANNELS],
+	age: u64,
+	fade: Option<f64>, // gain left while fading out
+}
+
+// equal power panning between the two channels closest to the position of the note
+fn pan_gains(pan: f64, nb_channels: usize) -> [f64; MAX_CHANNELS] {
+	let mut gains = [0.0; MAX_CHANNELS];
+	let nb_channels = nb_channels.min(MAX_CHANNELS);
+	if nb_channels < 2 {
+		gains[0] = 1.0;
+		return gains;
+	}
+	let pos = (pan.max(-1.0).min(1.0) + 1.0) / 2.0 * (nb_channels - 1) as f64;
+	let i = (pos as usize).min(nb_channels - 2);
+	let t = pos - i as f64;
+	gains[i] = (t * std::f64::consts::FRAC_PI_2).cos();
+	gains[i + 1] = (t * std::f64::consts::FRAC_PI_2).sin();
+	gains
 }
 
 impl Voice {
-	pub fn new(note: Note) -> Voice {
+	pub fn new(note: Note, age: u64, nb_channels: usize, patch: &Patch, spectrum: &Spectrum, resources: &Resources) -> Voice {
+		let envelope = patch.envelope.clamped();
+		let source = match patch.waveform {
+			Waveform::Sine => Source::Osc(Osc::new(osc::Mode::Sine)),
+			Waveform::Saw => Source::Osc(Osc::new(osc::Mode::Saw)),
+			Waveform::Square => Source::Osc(Osc::new(osc::Mode::Square)),
+			Waveform::Additive => Source::Additive(Additive::new(spectrum.clone())),
+			Waveform::Sample => match sampler::closest(&resources.samples, note.freq) {
+				Some(sample) => Source::Sampler(Sampler::new(sample)),
+				None => Source::Silence,
+			},
+			Waveform::Wavetable => match &resources.wavetable {
+				Some(table) => Source::Wavetable(WavetableOsc::new(table.clone(), patch.wavetable.position)),
+				None => Source::Silence,
+			},
+			Waveform::Fm => Source::Fm(Fm::new(patch.operators.clone())),
+		};
 		Voice {
-			osc: Osc::new(osc::Mode::Saw),
+			source,
 			note,
-			adsr: ADSR::new(ATTACK, DECAY, SUSTAIN, RELEASE),
+			adsr: ADSR::new(envelope.attack, envelope.decay, envelope.sustain, envelope.release),
+			gains: pan_gains(note.pan, nb_channels),
+			age,
+			fade: None,
 		}
 	}
 
 	pub fn next(&mut self, delta: f64) -> f64 {
 		let mut out = 0.0;
-		out += self.osc.next(delta * self.note.freq);
+		out += self.source.next(delta * self.note.freq);
 		out *= self.adsr.sample();
 		self.adsr.advance(delta);
+		if let Some(fade) = &mut self.fade {
+			out *= *fade;
+			*fade -= delta / FADE_TIME;
+		}
 		out
 	}
+
+	pub fn is_dead(&self) -> bool {
+		self.adsr.state == adsr::Dead || self.fade.map_or(false, |fade| fade <= 0.0)
+	}
+
+	// voices fading out are already on their way out
+	pub fn is_playing(&self) -> bool {
+		self.fade.is_none()
+	}
+
+	pub fn is_released(&self) -> bool {
+		matches!(self.adsr.state, adsr::Release(_))
+	}
+
+	pub fn stop(&mut self) {
+		if self.fade.is_none() {
+			self.fade = Some(1.0);
+		}
+	}
 }
 
+// the cutoff is converted from Hz, so that the sound doesn't depend on the sample rate
+fn make_filter(filter: &Filter, period: f64) -> svf::Kernel {
+	let f = (filter.cutoff * period).max(0.0).min(0.49);
+	let filter = filter.clamped();
+	match filter.kind {
+		FilterKind::Lowpass => svf::lowpass(f, filter.resonance),
+		FilterKind::Bandpass => svf::bandpass(f, filter.resonance),
+		FilterKind::Highpass => svf::highpass(f, filter.resonance),
+	}
+}
+
+const METER_BLOCK: usize = 1024; // in frames
+
+pub const MAX_POLYPHONY: usize = 64;
+
+const PENDING_CAPACITY: usize = 1024; // cues waiting for their frame, the queue never grows past it
+
+// what can be scheduled at a given frame
+#[derive(Clone, Copy)]
+enum Cue {
+	Event(Event),
+	Click(Click),
+}
+
+// ordered so that the queue gives the earliest cue first, and the cues due at the same frame in the order they came
+struct Pending {
+	due: u64,
+	order: u64,
+	cue: Cue,
+}
+
+impl Ord for Pending {
+	fn cmp(&self, other: &Pending) -> Ordering {
+		(other.due, other.order).cmp(&(self.due, self.order))
+	}
+}
+impl PartialOrd for Pending {
+	fn partial_cmp(&self, other: &Pending) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl PartialEq for Pending {
+	fn eq(&self, other: &Pending) -> bool {
+		(self.due, self.order) == (other.due, other.order)
+	}
+}
+impl Eq for Pending {}
+
 pub struct Synth {
 	pub period: f64,
-	voices: Vec<Voice>,
-	lowpass: svf::Kernel,
+	patch: Arc<Patch>,
+	spectrum: Spectrum,
+	resources: Arc<Resources>,
+	voices: Vec<Option<Voice>>, // preallocated, so that notes don't allocate in the audio thread
+	next_age: u64,
+	time: u64, // in frames since the start
+	pending: BinaryHeap<Pending>,
+	next_order: u64,
+	click: ClickOsc,
+	// one per channel
+	mix: Vec<f64>,
+	filters: Vec<svf::Kernel>,
+	delays: Vec<DelayLine>,
+	reverbs: Vec<Freeverb>,
 	limiter: Limiter,
+	meters: Option<Arc<Meters>>,
+	levels: Vec<(f64, f64)>, // peak and sum of squares of each channel, for the meters
+	level_frames: usize,
+	bin: Bin, // the patches, resources and voices that are replaced
 }
 
 impl Synth {
-	pub fn new(period: f64) -> Synth {
+	pub fn new(period: f64, nb_channels: usize) -> Synth {
+		let patch = Arc::new(Patch::default());
 		Synth {
 			period,
-			voices: vec![],
-			lowpass: svf::lowpass(0.02, 0.3),
-			limiter: Limiter::new(),
+			mix: vec![0.0; nb_channels],
+			filters: (0..nb_channels).map(|_| make_filter(&patch.filter, period)).collect(),
+			delays: (0..nb_channels).map(|_| DelayLine::new(period)).collect(),
+			reverbs: (0..nb_channels).map(|ch| Freeverb::new(period, ch)).collect(),
+			limiter: Limiter::new(period, nb_channels),
+			meters: None,
+			levels: vec![(0.0, 0.0); nb_channels],
+			level_frames: 0,
+			spectrum: patch.partials.spectrum(2.0),
+			resources: Arc::new(Resources::default()),
+			patch,
+			voices: (0..MAX_POLYPHONY).map(|_| None).collect(),
+			next_age: 0,
+			time: 0,
+			pending: BinaryHeap::with_capacity(PENDING_CAPACITY),
+			next_order: 0,
+			click: ClickOsc::new(period),
+			bin: Bin::default(),
 		}
 	}
 
+	pub fn set_bin(&mut self, bin: Bin) {
+		self.bin = bin;
+	}
+
+	pub fn set_patch(&mut self, patch: Arc<Patch>) {
+		if patch.filter != self.patch.filter {
+			for filter in &mut self.filters {
+				*filter = make_filter(&patch.filter, self.period);
+			}
+		}
+		let (delay, reverb) = (&patch.effects.delay, &patch.effects.reverb);
+		for line in &mut self.delays {
+			// what was left in the buffers would come back when enabling the effects again
+			if delay.enabled && !self.patch.effects.delay.enabled {
+				line.clear();
+			}
+			line.set(delay, self.period);
+		}
+		for freeverb in &mut self.reverbs {
+			if reverb.enabled && !self.patch.effects.reverb.enabled {
+				freeverb.clear();
+			}
+			freeverb.set(reverb);
+		}
+		// partials that depend on the layout are resolved by the frontend, octaves are assumed otherwise
+		let spectrum = std::mem::replace(&mut self.spectrum, patch.partials.spectrum(2.0));
+		self.bin.throw(Garbage::Spectrum(spectrum));
+		let patch = std::mem::replace(&mut self.patch, patch);
+		self.bin.throw(Garbage::Patch(patch));
+	}
+
+	pub fn set_meters(&mut self, meters: Arc<Meters>) {
+		meters.set_nb_channels(self.mix.len());
+		self.meters = Some(meters);
+	}
+
+	pub fn set_resources(&mut self, resources: Arc<Resources>) {
+		let resources = std::mem::replace(&mut self.resources, resources);
+		self.bin.throw(Garbage::Resources(resources));
+	}
+
 	pub fn add_voice(&mut self, note: Note) {
-		self.voices.push(Voice::new(note));
+		let polyphony = self.patch.voices.polyphony.max(1).min(MAX_POLYPHONY);
+		let playing = self.voices.iter().flatten().filter(|voice| voice.is_playing()).count();
+		if playing >= polyphony {
+			if let Some(idx) = self.victim() {
+				self.voices[idx].as_mut().unwrap().stop();
+			}
+		}
+		// when every slot is taken, the voice closest to the end of its fade is cut short
+		let slot = match self.voices.iter().position(Option::is_none) {
+			Some(slot) => slot,
+			None => (0..self.voices.len())
+				.min_by(|&a, &b| {
+					let fade = |idx: usize| self.voices[idx].as_ref().and_then(|voice| voice.fade).unwrap_or(2.0);
+					fade(a).partial_cmp(&fade(b)).unwrap_or(Ordering::Equal)
+				})
+				.unwrap(),
+		};
+		let age = self.next_age;
+		self.next_age += 1;
+		let voice = Voice::new(note, age, self.mix.len(), &self.patch, &self.spectrum, &self.resources);
+		if let Some(voice) = self.voices[slot].replace(voice) {
+			self.bin.throw(Garbage::Voice(voice));
+		}
 	}
 
-	pub fn process_events(&mut self, events: &[Event]) {
-		for &event in events {
-			match event {
-				Event::NotePlay(note) => {
-					self.add_voice(note);
-				}
-				Event::NoteStop(note_id) => {
-					for voice in &mut self.voices {
-						if voice.note.id == note_id {
-							voice.adsr.release();
-						}
+	// the slot of the playing voice to steal, according to the patch
+	fn victim(&self) -> Option<usize> {
+		let playing = || {
+			self.voices
+				.iter()
+				.enumerate()
+				.filter_map(|(idx, voice)| voice.as_ref().filter(|voice| voice.is_playing()).map(|voice| (idx, voice)))
+		};
+		let age = |(_, voice): &(usize, &Voice)| voice.age;
+		let victim = match self.patch.voices.stealing {
+			Stealing::Oldest => playing().min_by_key(age),
+			Stealing::Quietest => playing().min_by(|(_, a), (_, b)| {
+				a.adsr.sample().partial_cmp(&b.adsr.sample()).unwrap_or(Ordering::Equal)
+			}),
+			Stealing::ReleasedFirst => playing()
+				.filter(|(_, voice)| voice.is_released())
+				.min_by_key(age)
+				.or_else(|| playing().min_by_key(age)),
+		};
+		victim.map(|(idx, _)| idx)
+	}
+
+	/// Plays `event` once `offset` seconds have been rendered from now
+	pub fn schedule(&mut self, offset: f64, event: Event) {
+		self.schedule_cue(offset, Cue::Event(event));
+	}
+
+	/// Plays a metronome click once `offset` seconds have been rendered from now
+	pub fn schedule_click(&mut self, offset: f64, click: Click) {
+		self.schedule_cue(offset, Cue::Click(click));
+	}
+
+	fn schedule_cue(&mut self, offset: f64, cue: Cue) {
+		// a full queue plays the cue right away, rather than growing or losing it
+		if self.pending.len() == PENDING_CAPACITY {
+			self.play_cue(cue);
+			return;
+		}
+		let due = self.time + (offset.max(0.0) / self.period).round() as u64;
+		self.pending.push(Pending {
+			due,
+			order: self.next_order,
+			cue,
+		});
+		self.next_order += 1;
+	}
+
+	fn process_events(&mut self) {
+		while self.pending.peek().map_or(false, |pending| pending.due <= self.time) {
+			let pending = self.pending.pop().unwrap();
+			self.play_cue(pending.cue);
+		}
+	}
+
+	fn play_cue(&mut self, cue: Cue) {
+		let event = match cue {
+			Cue::Event(event) => event,
+			Cue::Click(click) => {
+				self.click.trigger(click);
+				return;
+			}
+		};
+		match event {
+			Event::NotePlay(note) => {
+				self.add_voice(note);
+			}
+			Event::NoteStop(note_id) => {
+				for voice in self.voices.iter_mut().flatten() {
+					if voice.note.id == note_id {
+						voice.adsr.release();
 					}
 				}
-				Event::NoteStopAll => {
-					self.voices = vec![];
+			}
+			Event::NoteStopAll => {
+				for voice in self.voices.iter_mut().flatten() {
+					voice.stop();
 				}
-				Event::NoteChangeFreq(note_id, freq) => {
-					for voice in &mut self.voices {
-						if voice.note.id == note_id {
-							voice.note.freq = freq;
-						}
+			}
+			Event::NoteChangeFreq(note_id, freq) => {
+				for voice in self.voices.iter_mut().flatten() {
+					if voice.note.id == note_id {
+						voice.note.freq = freq;
 					}
 				}
 			}
 		}
 	}
 
-	pub fn next_sample(&mut self) -> f64 {
-		let mut out = 0.0;
+	/// Adds the next frame to `frame`, which has one sample per channel
+	pub fn next_frame(&mut self, frame: &mut [f64]) {
+		self.process_events();
+		self.time += 1;
+
+		for x in &mut self.mix {
+			*x = 0.0;
+		}
+
+		for slot in &mut self.voices {
+			if let Some(voice) = slot {
+				let out = voice.next(self.period);
+				for (x, gain) in self.mix.iter_mut().zip(&voice.gains) {
+					*x += out * gain;
+				}
+				if voice.is_dead() {
+					if let Some(voice) = slot.take() {
+						self.bin.throw(Garbage::Voice(voice));
+					}
+				}
+			}
+		}
+
+		let effects = &self.patch.effects;
+		for (ch, x) in self.mix.iter_mut().enumerate() {
+			let mut y = self.filters[ch].eval(*x);
+			if effects.delay.enabled {
+				y = self.delays[ch].eval(y);
+			}
+			if effects.reverb.enabled {
+				y = self.reverbs[ch].eval(y);
+			}
+			*x = y * self.patch.gain;
+		}
 
-		for voice in &mut self.voices {
-			out += voice.next(self.period);
+		// the metronome is left out of the effects
+		let click = self.click.next(self.period);
+		for x in &mut self.mix {
+			*x += click;
 		}
 
-		self.voices.retain(|voice| voice.adsr.state != adsr::Dead);
+		self.measure();
+		self.limiter.eval(&mut self.mix);
 
-		out = self.lowpass.eval(out);
-		out = self.limiter.eval(out);
-		out * 0.8
+		for (out, x) in frame.iter_mut().zip(&self.mix) {
+			*out += x;
+		}
+	}
+
+	// the levels are measured before the limiter, to show when it is working
+	fn measure(&mut self) {
+		let meters = match &self.meters {
+			Some(meters) => meters,
+			None => return,
+		};
+		for ((peak, sum), x) in self.levels.iter_mut().zip(&self.mix) {
+			*peak = peak.max(x.abs());
+			*sum += x * x;
+		}
+		self.level_frames += 1;
+		if self.level_frames == METER_BLOCK {
+			for (ch, (peak, sum)) in self.levels.iter_mut().enumerate() {
+				meters.publish(ch, *peak, (*sum / METER_BLOCK as f64).sqrt());
+				*peak = 0.0;
+				*sum = 0.0;
+			}
+			self.level_frames = 0;
+		}
 	}
 }
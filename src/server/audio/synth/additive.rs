@@ -0,0 +1,36 @@
+use crate::data::synth::Spectrum;
+
+const TAU: f64 = std::f64::consts::PI * 2.0;
+
+pub const MAX_PARTIALS: usize = 64; // the others are ignored, the phases are kept inline so that voices don't allocate
+
+/// A sum of sines, one per partial of the spectrum
+pub struct Additive {
+	spectrum: Spectrum,
+	phases: [f64; MAX_PARTIALS],
+	norm: f64,
+}
+
+impl Additive {
+	pub fn new(spectrum: Spectrum) -> Additive {
+		let total: f64 = spectrum.0.iter().take(MAX_PARTIALS).map(|(_, amplitude)| amplitude.abs()).sum();
+		Additive {
+			phases: [0.0; MAX_PARTIALS],
+			norm: if total > 0.0 { 1.0 / total } else { 0.0 },
+			spectrum,
+		}
+	}
+
+	pub fn next(&mut self, delta: f64) -> f64 {
+		let mut out = 0.0;
+		for (phase, &(ratio, amplitude)) in self.phases.iter_mut().zip(self.spectrum.0.iter()) {
+			let step = delta * ratio;
+			// partials above nyquist would alias
+			if step < 0.5 {
+				out += amplitude * (*phase * TAU).sin();
+			}
+			*phase = (*phase + step).fract();
+		}
+		out * self.norm
+	}
+}
@@ -0,0 +1,46 @@
+//! The sound of the metronome, a short sine with a fast decay
+
+use crate::data::layout::Click;
+
+const TAU: f64 = std::f64::consts::PI * 2.0;
+const DECAY: f64 = 0.02; // in seconds, for the level to fall by a factor of e
+const SILENCE: f64 = 1e-4;
+
+pub struct ClickOsc {
+	phase: f64,
+	freq: f64,
+	level: f64,
+	decay: f64, // per frame
+}
+
+impl ClickOsc {
+	pub fn new(period: f64) -> ClickOsc {
+		ClickOsc {
+			phase: 0.0,
+			freq: 0.0,
+			level: 0.0,
+			decay: (-period / DECAY).exp(),
+		}
+	}
+
+	pub fn trigger(&mut self, click: Click) {
+		let (freq, level) = match click {
+			Click::Bar => (1760.0, 0.5),
+			Click::Beat => (880.0, 0.35),
+			Click::Subdivision => (660.0, 0.15),
+		};
+		self.phase = 0.0;
+		self.freq = freq;
+		self.level = level;
+	}
+
+	pub fn next(&mut self, period: f64) -> f64 {
+		if self.level < SILENCE {
+			return 0.0;
+		}
+		let out = self.level * (self.phase * TAU).sin();
+		self.phase = (self.phase + self.freq * period).fract();
+		self.level *= self.decay;
+		out
+	}
+}
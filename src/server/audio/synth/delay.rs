@@ -0,0 +1,44 @@
+use crate::data::synth::Delay;
+
+const MAX_TIME: f64 = 4.0; // in seconds
+
+/// A feedback delay, its buffer is allocated once for the longest delay time
+pub struct DelayLine {
+	buf: Vec<f64>,
+	pos: usize,
+	len: usize,
+	feedback: f64,
+	mix: f64,
+}
+
+impl DelayLine {
+	pub fn new(period: f64) -> DelayLine {
+		DelayLine {
+			buf: vec![0.0; (MAX_TIME / period) as usize + 1],
+			pos: 0,
+			len: 1,
+			feedback: 0.0,
+			mix: 0.0,
+		}
+	}
+
+	pub fn set(&mut self, delay: &Delay, period: f64) {
+		self.len = ((delay.time / period) as usize).max(1).min(self.buf.len());
+		self.feedback = delay.feedback.max(0.0).min(0.95);
+		self.mix = delay.mix;
+	}
+
+	pub fn clear(&mut self) {
+		for x in &mut self.buf {
+			*x = 0.0;
+		}
+	}
+
+	pub fn eval(&mut self, input: f64) -> f64 {
+		let read = (self.pos + self.buf.len() - self.len) % self.buf.len();
+		let delayed = self.buf[read];
+		self.buf[self.pos] = input + delayed * self.feedback;
+		self.pos = (self.pos + 1) % self.buf.len();
+		input + delayed * self.mix
+	}
+}
@@ -0,0 +1,32 @@
+use crate::data::synth::Operators;
+
+const TAU: f64 = std::f64::consts::PI * 2.0;
+
+/// A stack of sine operators, each one modulating the phase of the previous one
+pub struct Fm {
+	operators: Operators,
+	phases: [f64; 4],
+}
+
+impl Fm {
+	pub fn new(operators: Operators) -> Fm {
+		Fm {
+			phases: [0.0; 4],
+			operators,
+		}
+	}
+
+	pub fn next(&mut self, delta: f64) -> f64 {
+		let mut modulation = 0.0;
+		for (phase, &(_, index)) in self.phases.iter().zip(self.operators.0.iter()).skip(1).rev() {
+			modulation = index * (*phase * TAU + modulation).sin();
+		}
+		let level = self.operators.0[0].1;
+		let out = level * (self.phases[0] * TAU + modulation).sin();
+
+		for (phase, &(ratio, _)) in self.phases.iter_mut().zip(self.operators.0.iter()) {
+			*phase = (*phase + delta * ratio).fract();
+		}
+		out
+	}
+}
@@ -1,29 +1,60 @@
-//! haha, I don't know what I'm doing
+//! Lookahead peak limiter, shared by all the channels so that the stereo image stays the same
 
-const LENGTH: usize = 2048;
+const LOOKAHEAD: f64 = 0.005; // in seconds, also the attack time
+const RELEASE: f64 = 0.1; // in seconds
+const THRESHOLD: f64 = 0.98;
 
 pub struct Limiter {
-	buf_pos: usize,
-	buf: [f64; LENGTH],
-	rms: f64,
+	nb_channels: usize,
+	len: usize,
+	pos: usize,
+	delayed: Vec<f64>, // `len` frames, the output is `len - 1` frames late
+	targets: Vec<f64>, // gain needed by each frame of the lookahead
+	gains: Vec<f64>,   // smoothed over the lookahead, so that the gain reaches its target in time
+	gains_sum: f64,
+	gain: f64,
+	release: f64,
 }
 
 impl Limiter {
-	pub fn new() -> Limiter {
+	pub fn new(period: f64, nb_channels: usize) -> Limiter {
+		let len = ((LOOKAHEAD / period) as usize).max(1);
 		Limiter {
-			buf_pos: 0,
-			buf: [0.0; LENGTH],
-			rms: 0.0,
+			nb_channels,
+			len,
+			pos: 0,
+			delayed: vec![0.0; len * nb_channels],
+			targets: vec![1.0; len],
+			gains: vec![1.0; len],
+			gains_sum: len as f64,
+			gain: 1.0,
+			release: 1.0 - (-period / RELEASE).exp(),
 		}
 	}
 
-	pub fn eval(&mut self, input: f64) -> f64 {
-		self.buf_pos = (self.buf_pos + 1) % LENGTH;
-		let old = self.buf[self.buf_pos];
-		self.buf[self.buf_pos] = input;
-		self.rms = ((self.rms * self.rms * LENGTH as f64 - old * old + input * input) / LENGTH as f64)
-			.max(0.0)
-			.sqrt();
-		input / self.rms.max(1.0)
+	/// Limits a frame in place, the output is delayed by the lookahead
+	pub fn eval(&mut self, frame: &mut [f64]) {
+		let peak = frame.iter().fold(0.0f64, |peak, x| peak.max(x.abs()));
+		self.targets[self.pos] = if peak > THRESHOLD { THRESHOLD / peak } else { 1.0 };
+
+		// the gain drops right away to what the loudest frame ahead needs, and recovers slowly
+		let target = self.targets.iter().cloned().fold(1.0, f64::min);
+		self.gain = if target < self.gain {
+			target
+		} else {
+			self.gain + (target - self.gain) * self.release
+		};
+		self.gains_sum += self.gain - self.gains[self.pos];
+		self.gains[self.pos] = self.gain;
+		let gain = self.gains_sum / self.len as f64;
+
+		// the frame coming out was the first one seen by every gain of the average
+		let nb_channels = self.nb_channels;
+		self.delayed[self.pos * nb_channels..(self.pos + 1) * nb_channels].copy_from_slice(&frame[..nb_channels]);
+		self.pos = (self.pos + 1) % self.len;
+		let delayed = &self.delayed[self.pos * nb_channels..(self.pos + 1) * nb_channels];
+		for (x, old) in frame.iter_mut().zip(delayed) {
+			*x = old * gain;
+		}
 	}
 }
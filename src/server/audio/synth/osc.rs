@@ -1,7 +1,6 @@
 const TAU: f64 = std::f64::consts::PI * 2.0;
 
-#[derive(Clone)]
-#[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub enum Mode {
 	Sine,
 	Saw,
@@ -0,0 +1,58 @@
+//! Files used by the patch, loaded outside of the audio thread
+
+use super::sampler::{self, Sample};
+use super::wavetable::{self, Table};
+use crate::data::synth::{Patch, SampleSet, Wavetable};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default)]
+pub struct Resources {
+	pub samples: Vec<Arc<Sample>>,
+	pub wavetable: Option<Arc<Table>>,
+}
+
+/// Keeps track of the loaded files, to only read them again when the patch points to other ones
+#[derive(Default)]
+pub struct Loader {
+	sample_set: SampleSet,
+	wavetable: Option<(String, usize)>,
+	resources: Resources,
+}
+
+impl Loader {
+	/// Returns the new resources if they changed, errors are reported through `on_error`
+	pub fn update(&mut self, patch: &Patch, mut on_error: impl FnMut(String)) -> Option<Arc<Resources>> {
+		let mut changed = false;
+		if patch.samples != self.sample_set {
+			match sampler::load(&patch.samples) {
+				Ok(samples) => {
+					self.sample_set = patch.samples.clone();
+					self.resources.samples = samples;
+					changed = true;
+				}
+				Err(err) => on_error(format!("Couldn't load the samples: {}", err)),
+			}
+		}
+		let Wavetable { path, frame_size, .. } = &patch.wavetable;
+		let file = Some((path.clone(), *frame_size)).filter(|(path, _)| !path.is_empty());
+		if file != self.wavetable {
+			let table = match &file {
+				Some(_) => wavetable::load(&patch.wavetable).map(|table| Some(Arc::new(table))),
+				None => Ok(None),
+			};
+			match table {
+				Ok(table) => {
+					self.wavetable = file;
+					self.resources.wavetable = table;
+					changed = true;
+				}
+				Err(err) => on_error(format!("Couldn't load the wavetable: {}", err)),
+			}
+		}
+		if changed {
+			Some(Arc::new(self.resources.clone()))
+		} else {
+			None
+		}
+	}
+}
@@ -0,0 +1,116 @@
+//! Freeverb, an algorithmic reverb made of parallel comb filters followed by allpass filters
+
+use crate::data::synth::Reverb;
+
+// delay lengths in samples at 44.1kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const TUNING_RATE: f64 = 44100.0;
+
+const INPUT_GAIN: f64 = 0.015;
+const WET_GAIN: f64 = 3.0;
+const SCALE_ROOM: f64 = 0.28;
+const OFFSET_ROOM: f64 = 0.7;
+const SCALE_DAMPING: f64 = 0.4;
+const ALLPASS_FEEDBACK: f64 = 0.5;
+
+struct Comb {
+	buf: Vec<f64>,
+	pos: usize,
+	store: f64,
+}
+
+impl Comb {
+	fn eval(&mut self, input: f64, feedback: f64, damping: f64) -> f64 {
+		let out = self.buf[self.pos];
+		self.store = out * (1.0 - damping) + self.store * damping;
+		self.buf[self.pos] = input + self.store * feedback;
+		self.pos = (self.pos + 1) % self.buf.len();
+		out
+	}
+}
+
+struct Allpass {
+	buf: Vec<f64>,
+	pos: usize,
+}
+
+impl Allpass {
+	fn eval(&mut self, input: f64) -> f64 {
+		let delayed = self.buf[self.pos];
+		self.buf[self.pos] = input + delayed * ALLPASS_FEEDBACK;
+		self.pos = (self.pos + 1) % self.buf.len();
+		delayed - input
+	}
+}
+
+pub struct Freeverb {
+	combs: Vec<Comb>,
+	allpasses: Vec<Allpass>,
+	feedback: f64,
+	damping: f64,
+	mix: f64,
+}
+
+impl Freeverb {
+	/// Every channel gets slightly different delays, so that the channels don't sound the same
+	pub fn new(period: f64, channel: usize) -> Freeverb {
+		let scale = 1.0 / (period * TUNING_RATE);
+		let spread = channel * STEREO_SPREAD;
+		let make_buf = |len: usize| vec![0.0; (((len + spread) as f64 * scale) as usize).max(1)];
+		Freeverb {
+			combs: COMB_TUNING
+				.iter()
+				.map(|&len| Comb {
+					buf: make_buf(len),
+					pos: 0,
+					store: 0.0,
+				})
+				.collect(),
+			allpasses: ALLPASS_TUNING
+				.iter()
+				.map(|&len| Allpass {
+					buf: make_buf(len),
+					pos: 0,
+				})
+				.collect(),
+			feedback: 0.0,
+			damping: 0.0,
+			mix: 0.0,
+		}
+	}
+
+	pub fn set(&mut self, reverb: &Reverb) {
+		self.feedback = reverb.size.max(0.0).min(1.0) * SCALE_ROOM + OFFSET_ROOM;
+		self.damping = reverb.damping.max(0.0).min(1.0) * SCALE_DAMPING;
+		self.mix = reverb.mix.max(0.0).min(1.0);
+	}
+
+	pub fn clear(&mut self) {
+		for comb in &mut self.combs {
+			comb.store = 0.0;
+			for x in &mut comb.buf {
+				*x = 0.0;
+			}
+		}
+		for allpass in &mut self.allpasses {
+			for x in &mut allpass.buf {
+				*x = 0.0;
+			}
+		}
+	}
+
+	pub fn eval(&mut self, input: f64) -> f64 {
+		let (feedback, damping) = (self.feedback, self.damping);
+		let mut wet = self
+			.combs
+			.iter_mut()
+			.map(|comb| comb.eval(input * INPUT_GAIN, feedback, damping))
+			.sum();
+		for allpass in &mut self.allpasses {
+			wet = allpass.eval(wet);
+		}
+		input * (1.0 - self.mix) + wet * WET_GAIN * self.mix
+	}
+}
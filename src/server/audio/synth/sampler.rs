@@ -0,0 +1,119 @@
+use crate::data::synth::SampleSet;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A decoded recording, mixed down to mono
+#[derive(Debug)]
+pub struct Sample {
+	pub root: f64,
+	pub rate: f64,
+	pub frames: Vec<f32>,
+	pub loop_range: Option<(usize, usize)>, // in frames
+}
+
+/// Decodes a WAV file mixed down to mono, along with its sample rate
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, f64), Box<dyn Error>> {
+	let mut reader = hound::WavReader::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+	let spec = reader.spec();
+	let data = match spec.sample_format {
+		hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<f32>, _>>()?,
+		hound::SampleFormat::Int => {
+			let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+			reader
+				.samples::<i32>()
+				.map(|x| x.map(|x| x as f32 * scale))
+				.collect::<Result<Vec<f32>, _>>()?
+		}
+	};
+	let nb_channels = spec.channels as usize;
+	let frames = data
+		.chunks(nb_channels)
+		.map(|frame| frame.iter().sum::<f32>() / nb_channels as f32)
+		.collect();
+	Ok((frames, f64::from(spec.sample_rate)))
+}
+
+pub fn load(set: &SampleSet) -> Result<Vec<Arc<Sample>>, Box<dyn Error>> {
+	set.0
+		.iter()
+		.map(|zone| {
+			let path = zone.path.display();
+			let (frames, rate) = read_wav(&zone.path)?;
+			let loop_range = match zone.loop_range {
+				Some((start, end)) => {
+					let (start, end) = ((start * rate) as usize, (end * rate) as usize);
+					if start >= end || end > frames.len() {
+						return Err(format!("{}: the loop points are outside of the recording", path).into());
+					}
+					Some((start, end))
+				}
+				None => None,
+			};
+			Ok(Arc::new(Sample {
+				root: zone.root,
+				rate,
+				frames,
+				loop_range,
+			}))
+		})
+		.collect()
+}
+
+// the sample that needs the least repitching
+pub fn closest(samples: &[Arc<Sample>], freq: f64) -> Option<Arc<Sample>> {
+	samples
+		.iter()
+		.min_by(|a, b| {
+			let da = (a.root / freq).ln().abs();
+			let db = (b.root / freq).ln().abs();
+			da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+		})
+		.cloned()
+}
+
+/// Plays a sample at any frequency, with cubic interpolation
+pub struct Sampler {
+	sample: Arc<Sample>,
+	pos: f64,
+}
+
+impl Sampler {
+	pub fn new(sample: Arc<Sample>) -> Sampler {
+		Sampler { sample, pos: 0.0 }
+	}
+
+	// `delta` is the number of periods of the note per output sample
+	pub fn next(&mut self, delta: f64) -> f64 {
+		let sample = &self.sample;
+		let len = sample.frames.len();
+		if self.pos >= len as f64 {
+			return 0.0;
+		}
+		let i = self.pos as usize;
+		let t = self.pos.fract();
+		let frame = |k: isize| -> f64 {
+			let mut k = i as isize + k;
+			if let Some((start, end)) = sample.loop_range {
+				if k >= end as isize {
+					k -= (end - start) as isize;
+				}
+			}
+			if k < 0 || k >= len as isize {
+				0.0
+			} else {
+				f64::from(sample.frames[k as usize])
+			}
+		};
+		let (y0, y1, y2, y3) = (frame(-1), frame(0), frame(1), frame(2));
+		let out = y1 + 0.5 * t * (y2 - y0 + t * (2.0 * y0 - 5.0 * y1 + 4.0 * y2 - y3 + t * (3.0 * (y1 - y2) + y3 - y0)));
+
+		self.pos += delta * sample.rate / sample.root;
+		if let Some((start, end)) = sample.loop_range {
+			while self.pos >= end as f64 {
+				self.pos -= (end - start) as f64;
+			}
+		}
+		out
+	}
+}
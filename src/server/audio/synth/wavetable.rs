@@ -0,0 +1,64 @@
+use super::sampler;
+use crate::data::synth::Wavetable;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Single-cycle waveforms of the same size
+#[derive(Debug)]
+pub struct Table {
+	frames: Vec<Vec<f32>>,
+}
+
+pub fn load(wavetable: &Wavetable) -> Result<Table, Box<dyn Error>> {
+	let (data, _) = sampler::read_wav(Path::new(&wavetable.path))?;
+	if data.is_empty() {
+		return Err(format!("{}: the file is empty", wavetable.path).into());
+	}
+	// a file shorter than a frame is a single cycle on its own
+	let frame_size = wavetable.frame_size.max(1).min(data.len());
+	let frames = data
+		.chunks_exact(frame_size)
+		.map(|frame| frame.to_vec())
+		.collect::<Vec<Vec<f32>>>();
+	Ok(Table { frames })
+}
+
+/// Plays the table, crossfading between the two frames around the position
+pub struct WavetableOsc {
+	table: Arc<Table>,
+	position: f64,
+	phase: f64,
+}
+
+impl WavetableOsc {
+	pub fn new(table: Arc<Table>, position: f64) -> WavetableOsc {
+		let position = position.max(0.0).min(1.0) * (table.frames.len() - 1) as f64;
+		WavetableOsc {
+			table,
+			position,
+			phase: 0.0,
+		}
+	}
+
+	pub fn next(&mut self, delta: f64) -> f64 {
+		let frames = &self.table.frames;
+		let i = self.position as usize;
+		let mix = self.position.fract();
+		let mut out = read(&frames[i], self.phase) * (1.0 - mix);
+		if mix > 0.0 {
+			out += read(&frames[i + 1], self.phase) * mix;
+		}
+		self.phase = (self.phase + delta).fract();
+		out
+	}
+}
+
+// linear interpolation within a cycle
+fn read(frame: &[f32], phase: f64) -> f64 {
+	let pos = phase * frame.len() as f64;
+	let i = pos as usize % frame.len();
+	let j = (i + 1) % frame.len();
+	let t = pos.fract();
+	f64::from(frame[i]) * (1.0 - t) + f64::from(frame[j]) * t
+}
@@ -0,0 +1,42 @@
+//! Values replaced on the audio thread, handed back to the server so that the callback never frees memory
+
+use super::audio;
+use crate::data::{
+	layout::Layout,
+	sheet::Sheet,
+	synth::{Patch, Spectrum},
+};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+
+// more than what a few updates replace, the bounded channel allocates its buffer once
+const CAPACITY: usize = 256;
+
+#[allow(dead_code)] // the values are only held until they are dropped
+pub enum Garbage {
+	Sheet(Sheet),
+	Layout(Layout),
+	Patch(Arc<Patch>),
+	Spectrum(Spectrum),
+	Resources(Arc<audio::Resources>),
+	Voice(audio::Voice),
+}
+
+/// Where the audio thread throws what it replaced, the default bin frees it in place
+#[derive(Clone, Default)]
+pub struct Bin(Option<SyncSender<Garbage>>);
+
+impl Bin {
+	/// Returns the bin along with the receiver that empties it
+	pub fn new() -> (Bin, Receiver<Garbage>) {
+		let (sender, receiver) = sync_channel(CAPACITY);
+		(Bin(Some(sender)), receiver)
+	}
+
+	pub fn throw(&self, garbage: Garbage) {
+		if let Some(sender) = &self.0 {
+			// when the bin is full or the server is gone, the value is freed here rather than waiting
+			sender.try_send(garbage).ok();
+		}
+	}
+}
@@ -0,0 +1,55 @@
+//! Output levels, written by the audio thread without locking and read by the UI
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+pub const MAX_CHANNELS: usize = 8;
+
+#[derive(Default)]
+struct Level {
+	peak: AtomicU64, // bits of a f64
+	rms: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct Meters {
+	nb_channels: AtomicUsize,
+	levels: [Level; MAX_CHANNELS],
+}
+
+impl Meters {
+	pub fn set_nb_channels(&self, nb_channels: usize) {
+		self.nb_channels.store(nb_channels.min(MAX_CHANNELS), Ordering::Relaxed);
+	}
+
+	pub fn nb_channels(&self) -> usize {
+		self.nb_channels.load(Ordering::Relaxed)
+	}
+
+	/// Reports the levels of a block, the peak is kept until it is read
+	pub fn publish(&self, channel: usize, peak: f64, rms: f64) {
+		if let Some(level) = self.levels.get(channel) {
+			let mut current = level.peak.load(Ordering::Relaxed);
+			while f64::from_bits(current) < peak {
+				match level
+					.peak
+					.compare_exchange_weak(current, peak.to_bits(), Ordering::Relaxed, Ordering::Relaxed)
+				{
+					Ok(_) => break,
+					Err(x) => current = x,
+				}
+			}
+			level.rms.store(rms.to_bits(), Ordering::Relaxed);
+		}
+	}
+
+	/// Returns the peak since the last read, and the last RMS level
+	pub fn read(&self, channel: usize) -> (f64, f64) {
+		match self.levels.get(channel) {
+			Some(level) => (
+				f64::from_bits(level.peak.swap(0, Ordering::Relaxed)),
+				f64::from_bits(level.rms.load(Ordering::Relaxed)),
+			),
+			None => (0.0, 0.0),
+		}
+	}
+}
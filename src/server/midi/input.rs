@@ -0,0 +1,66 @@
+//! Notes played on a MIDI controller, including MPE controllers that bend each note on its own channel
+
+use midir::{MidiInput, MidiInputConnection, MidiInputPort};
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Message {
+	NoteOn {
+		channel: u8,
+		key: u8,
+		velocity: u8,
+	},
+	NoteOff {
+		channel: u8,
+		key: u8,
+	},
+	/// From -1 to 1, the range in semitones depends on the controller
+	PitchBend {
+		channel: u8,
+		bend: f64,
+	},
+}
+
+impl Message {
+	pub fn parse(bytes: &[u8]) -> Option<Message> {
+		let status = *bytes.get(0)?;
+		let channel = status & 0x0F;
+		match (status & 0xF0, bytes.get(1).copied(), bytes.get(2).copied()) {
+			(0x90, Some(key), Some(velocity)) if velocity > 0 => Some(Message::NoteOn { channel, key, velocity }),
+			// a note on without velocity is a note off
+			(0x80, Some(key), _) | (0x90, Some(key), _) => Some(Message::NoteOff { channel, key }),
+			(0xE0, Some(lsb), Some(msb)) => {
+				let value = (u16::from(msb) << 7) | u16::from(lsb);
+				let bend = (f64::from(value) - 8192.0) / 8192.0;
+				Some(Message::PitchBend { channel, bend })
+			}
+			_ => None,
+		}
+	}
+}
+
+pub fn list_ports() -> Result<(Vec<MidiInputPort>, Vec<String>), Box<dyn Error>> {
+	let midi = MidiInput::new("harmoxen input")?;
+	let ports = midi.ports();
+	let port_names = ports.iter().map(|p| midi.port_name(p)).collect::<Result<_, _>>()?;
+	Ok((ports, port_names))
+}
+
+/// Calls `on_message` from the thread of the connection, until it is dropped
+pub fn connect(
+	port: &MidiInputPort,
+	mut on_message: impl FnMut(Message) + Send + 'static,
+) -> Result<MidiInputConnection<()>, Box<dyn Error>> {
+	let midi = MidiInput::new("harmoxen input")?;
+	let conn = midi.connect(
+		port,
+		"harmoxen input",
+		move |_, bytes, _| {
+			if let Some(message) = Message::parse(bytes) {
+				on_message(message);
+			}
+		},
+		(),
+	)?;
+	Ok(conn)
+}
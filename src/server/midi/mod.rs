@@ -1,110 +1,84 @@
-use super::Event;
-use crate::data::{icp, sheet::*};
-use crate::util::*;
+use super::{Backend, Notification};
+use crate::data::icp;
 use midir::{MidiOutput, MidiOutputConnection, MidiOutputPort};
 use std::error::Error;
-use std::sync::mpsc::*;
-use std::thread;
-use std::time::{Duration, Instant};
-
-pub fn launch(port: MidiOutputPort) -> Result<Sender<Event>, Box<dyn Error>> {
-	let (sender, receiver) = channel();
-	thread::spawn(move || {
-		if let Err(err) = run(receiver, port) {
-			println!("Error with the mpe server: {}", err);
-		}
-	});
-	Ok(sender)
+use std::ops::RangeInclusive;
+use std::sync::mpsc::Sender;
+
+pub mod input;
+
+/// How the notes are sent through the port
+#[derive(Clone)]
+pub struct MidiConfig {
+	pub port: MidiOutputPort,
+	/// The synth is set up as an MPE zone on channels 2 to 16, otherwise notes are rotated over `channels`
+	pub mpe: bool,
+	pub channels: RangeInclusive<u8>, // from 0 to 15
+	pub bend_range: f64,              // in semitones
+	pub stealing: bool,               // the oldest note is cut when all the channels are playing
 }
 
-const UPDATE_RATE: f64 = 0.04;
-
-pub fn run(receiver: Receiver<Event>, port: MidiOutputPort) -> Result<(), Box<dyn Error>> {
-	let mut engine = Engine::new(port)?;
-
-	let mut last_instant = Instant::now();
-	let mut until_update = 0.0;
-
-	let mut running = true;
-	while running {
-		let new_instant = Instant::now();
-		let dt = new_instant.duration_since(last_instant).as_nanos() as f64 / 1000000000.0;
-		last_instant = new_instant;
-
-		until_update += dt;
-
-		while until_update > UPDATE_RATE as f64 {
-			while let Ok(event) = receiver.try_recv() {
-				match event {
-					Event::SetTempo(t) => {
-						engine.tempo = t;
-					}
-					Event::PlayStart(sheet, start) => {
-						engine.cursor = start;
-						engine.setup_mpe().unwrap();
-						for event in sheet.get_events_at_time(start) {
-							engine.process_icp(event);
-						}
-						engine.sheet = sheet;
-						engine.active = true;
-					}
-					Event::PlayStop => {
-						engine.process_icp(icp::Event::NoteStopAll);
-						engine.active = false;
-					}
-					Event::SheetChanged(sheet) => {
-						engine.sheet = sheet;
-					}
-					Event::ICP(event) => {
-						engine.process_icp(event);
-					}
-					Event::Shutdown => {
-						running = false;
-					}
-				}
-			}
-			if engine.active {
-				engine.update(UPDATE_RATE);
-			}
-			until_update -= UPDATE_RATE as f64;
-		}
-		thread::sleep(Duration::from_millis((until_update * 1000.0) as u64));
-	}
-	Ok(())
-}
-
-const PITCH_BEND_RANGE: f64 = 2.0; // in semitones
-const PITCH_BEND_SEMITONES: u8 = PITCH_BEND_RANGE as u8;
-const PITCH_BEND_CENTS: u8 = ((PITCH_BEND_RANGE % 1.0) * 100.0) as u8;
-
 #[derive(Clone, Copy, Default)]
 struct Channel {
+	number: u8, // from 0 to 15
 	current: Option<icp::NoteId>,
 	note_number: u8,
+	pan: f64,
+	pressure: f64,
+	timbre: f64,
+	age: u64,      // when the note started, to find the oldest one
+	released: u64, // when the last note ended, so that its release can ring before the channel is used again
 }
 
-struct Engine {
+pub struct Mpe {
 	conn: MidiOutputConnection,
-	tempo: f64,
-	active: bool,
-	cursor: f64,
-	sheet: Sheet,
 	channels: Vec<Channel>,
+	bend_range: f64,
+	stealing: bool,
+	to_frontend: Sender<Notification>,
+	overflowed: bool, // the frontend was told that notes are missing
+	notes_played: u64,
+	notes_released: u64,
 }
 
-impl Engine {
-	pub fn new(port: MidiOutputPort) -> Result<Engine, Box<dyn Error>> {
+impl Backend for Mpe {
+	fn process(&mut self, _offset: f64, event: icp::Event) -> Result<(), Box<dyn Error>> {
+		self.process_icp(event)
+	}
+}
+
+impl Mpe {
+	pub fn new(config: MidiConfig, to_frontend: Sender<Notification>) -> Result<Mpe, Box<dyn Error>> {
 		let midi_out = MidiOutput::new("midir mpe output")?;
-		let conn = midi_out.connect(&port, "midir mpe")?;
+		let conn = midi_out.connect(&config.port, "midir mpe")?;
 
-		Ok(Engine {
+		let numbers = if config.mpe { 1..=15 } else { config.channels.clone() };
+		let mut mpe = Mpe {
 			conn,
-			tempo: 140.0,
-			active: false,
-			cursor: 0.0,
-			sheet: Sheet::default(),
-			channels: vec![Channel::default(); 15],
-		})
+			channels: numbers
+				.map(|number| Channel {
+					number,
+					..Channel::default()
+				})
+				.collect(),
+			bend_range: config.bend_range,
+			stealing: config.stealing,
+			to_frontend,
+			overflowed: false,
+			notes_played: 0,
+			notes_released: 0,
+		};
+		if mpe.channels.is_empty() {
+			return Err("there are no MIDI channels to play on".into());
+		}
+		if mpe.bend_range.is_nan() || mpe.bend_range <= 0.0 {
+			return Err("the pitch bend range must be above 0".into());
+		}
+		if config.mpe {
+			mpe.setup_mpe()?;
+		}
+		mpe.setup_channels()?;
+		Ok(mpe)
 	}
 
 	pub fn setup_mpe(&mut self) -> Result<(), Box<dyn Error>> {
@@ -116,14 +90,22 @@ impl Engine {
 		self.conn.send(&[0xB0, 0x79, 0x00])?;
 		self.conn.send(&[0xB0, 0x64, 0x06])?;
 		self.conn.send(&[0xB0, 0x65, 0x00])?;
-		self.conn.send(&[0xB0, 0x06, 0x07])?;
+		// the lower zone takes all the channels the notes are spread over, after the master channel
+		self.conn.send(&[0xB0, 0x06, self.channels.len() as u8])?;
 
-		for i in 0..16 {
+		Ok(())
+	}
+
+	// synths without RPN support keep their own bend range, which has to match the one in the settings
+	fn setup_channels(&mut self) -> Result<(), Box<dyn Error>> {
+		let semitones = self.bend_range.trunc() as u8;
+		let cents = (self.bend_range.fract() * 100.0).round() as u8;
+		for i in self.channels.iter().map(|ch| ch.number) {
 			// PITCH BEND RANGE SETUP
 			self.conn.send(&[0xB0 + i, 0x64, 0x00])?; // start control
 			self.conn.send(&[0xB0 + i, 0x65, 0x00])?;
-			self.conn.send(&[0xB0 + i, 0x06, PITCH_BEND_SEMITONES])?;
-			self.conn.send(&[0xB0 + i, 0x26, PITCH_BEND_CENTS])?;
+			self.conn.send(&[0xB0 + i, 0x06, semitones])?;
+			self.conn.send(&[0xB0 + i, 0x26, cents])?;
 			self.conn.send(&[0xB0 + i, 0x64, 0x7F])?; // stop control
 			self.conn.send(&[0xB0 + i, 0x65, 0x7F])?;
 			self.conn.send(&[0xE0 + i, 0b0000000, 0b1000000])?;
@@ -132,75 +114,132 @@ impl Engine {
 		Ok(())
 	}
 
-	pub fn update(&mut self, dt: f64) {
-		let length = dt * (self.tempo / 60.0);
-		let range = Range(self.cursor, self.cursor + length);
-		self.cursor += length;
-		let mut events = self.sheet.get_events(range);
-		if self.cursor > self.sheet.get_size() {
-			self.cursor %= self.sheet.get_size();
-			events.extend(self.sheet.get_events(Range(0.0, self.cursor)));
-		}
-		for event in events {
-			self.process_icp(event);
-		}
-	}
-
-	fn process_icp(&mut self, event: icp::Event) {
+	fn process_icp(&mut self, event: icp::Event) -> Result<(), Box<dyn Error>> {
 		match event {
 			icp::Event::NotePlay(note) => {
-				let free = self.channels.iter().position(|x| x.current == None);
-				if let Some(ch) = free {
-					self.note_on(ch, note).unwrap();
+				let free = (0..self.channels.len())
+					.filter(|&ch| self.channels[ch].current == None)
+					.min_by_key(|&ch| self.channels[ch].released);
+				match free {
+					Some(ch) => self.note_on(ch, note)?,
+					None => {
+						if !self.overflowed {
+							self.overflowed = true;
+							let message = if self.stealing {
+								format!(
+									"More notes are playing than there are MIDI channels ({}), the oldest ones are cut",
+									self.channels.len()
+								)
+							} else {
+								format!(
+									"More notes are playing than there are MIDI channels ({}), some of them are not heard",
+									self.channels.len()
+								)
+							};
+							self.to_frontend.send(Notification::Warning(message)).ok();
+						}
+						if self.stealing {
+							let oldest = (0..self.channels.len()).min_by_key(|&ch| self.channels[ch].age).unwrap();
+							self.note_off(oldest)?;
+							self.note_on(oldest, note)?;
+						}
+					}
 				}
 			}
 			icp::Event::NoteStop(id) => {
 				for ch in 0..self.channels.len() {
 					if self.channels[ch].current == Some(id) {
-						self.note_off(ch).unwrap();
+						self.note_off(ch)?;
 					}
 				}
 			}
 			icp::Event::NoteStopAll => {
 				for ch in 0..self.channels.len() {
-					self.note_off(ch).unwrap();
+					self.note_off(ch)?;
 				}
+				self.overflowed = false;
 			}
 			icp::Event::NoteChangeFreq(id, freq) => {
 				for ch in 0..self.channels.len() {
 					let channel = self.channels[ch];
 					if channel.current == Some(id) {
-						let pitch_bend = ((freq / 440.0).log2() * 12.0 + 69.0) - channel.note_number as f64;
-						if pitch_bend.abs() < PITCH_BEND_RANGE {
-							self.conn.send(&pitch_bend_msg(ch, pitch_bend)).unwrap();
+						let pitch_bend = match midi_pitch(freq) {
+							Some(pitch) => pitch - channel.note_number as f64,
+							None => continue,
+						};
+						if pitch_bend.abs() < self.bend_range {
+							let msg = self.pitch_bend_msg(ch, pitch_bend);
+							self.conn.send(&msg)?;
 						} else {
-							self.note_off(ch).unwrap();
-							self.note_on(ch, icp::Note { id, freq }).unwrap();
+							self.note_off(ch)?;
+							let note = icp::Note {
+								id,
+								freq,
+								pan: channel.pan,
+								pressure: channel.pressure,
+								timbre: channel.timbre,
+							};
+							self.note_on(ch, note)?;
 						}
 					}
 				}
 			}
 		}
+		Ok(())
 	}
 
 	fn note_on(&mut self, ch: usize, note: icp::Note) -> Result<(), Box<dyn Error>> {
-		let note_number = ((note.freq / 440.0).log2() * 12.0 + 69.0) as u8;
-		let pitch_bend = ((note.freq / 440.0).log2() * 12.0 + 69.0) - note_number as f64;
-		self.channels[ch].current = Some(note.id);
-		self.channels[ch].note_number = note_number;
-		self.conn.send(&pitch_bend_msg(ch, pitch_bend))?;
-		self.conn.send(&[0x91 + ch as u8, note_number, 0x70])?;
+		let pitch = match midi_pitch(note.freq) {
+			Some(pitch) => pitch,
+			None => return Ok(()),
+		};
+		let note_number = pitch as u8;
+		let pitch_bend = pitch - note_number as f64;
+		self.notes_played += 1;
+		let channel = &mut self.channels[ch];
+		channel.current = Some(note.id);
+		channel.note_number = note_number;
+		channel.pan = note.pan;
+		channel.pressure = note.pressure;
+		channel.timbre = note.timbre;
+		channel.age = self.notes_played;
+		let number = channel.number;
+		let msg = self.pitch_bend_msg(ch, pitch_bend);
+		self.conn.send(&msg)?;
+		self.conn.send(&[0xB0 + number, 10, ((note.pan + 1.0) * 63.5).max(0.0).min(127.0) as u8])?; // pan
+		// the expression is set before the note starts, like MPE controllers do
+		self.conn.send(&[0xB0 + number, 74, (note.timbre * 127.0).max(0.0).min(127.0) as u8])?; // timbre
+		self.conn.send(&[0xD0 + number, (note.pressure * 127.0).max(0.0).min(127.0) as u8])?; // channel pressure
+		self.conn.send(&[0x90 + number, note_number, 0x70])?;
 		Ok(())
 	}
 
 	fn note_off(&mut self, ch: usize) -> Result<(), Box<dyn Error>> {
-		self.channels[ch].current = None;
-		self.conn.send(&[0x81 + ch as u8, self.channels[ch].note_number, 0x70])?;
+		let channel = &mut self.channels[ch];
+		if channel.current.take().is_some() {
+			self.notes_released += 1;
+			channel.released = self.notes_released;
+		}
+		self.conn.send(&[0x80 + channel.number, channel.note_number, 0x70])?;
 		Ok(())
 	}
+
+	fn pitch_bend_msg(&self, ch: usize, t: f64) -> [u8; 3] {
+		let n = (t * 8191.0 / self.bend_range + 8192.0).max(0.0).min(16383.0) as usize;
+		[0xE0 + self.channels[ch].number, (n & 0b1111111) as u8, (n >> 7 & 0b1111111) as u8]
+	}
 }
 
-fn pitch_bend_msg(ch: usize, t: f64) -> [u8; 3] {
-	let n = (t * 8191.0 / PITCH_BEND_RANGE + 8192.0) as usize;
-	[0xE1 + ch as u8, (n & 0b1111111) as u8, (n >> 7 & 0b1111111) as u8]
+/// The pitch of a frequency in MIDI note numbers, folded by octaves into the range of MIDI notes
+fn midi_pitch(freq: f64) -> Option<f64> {
+	let pitch = (freq / 440.0).log2() * 12.0 + 69.0;
+	if !pitch.is_finite() {
+		None
+	} else if pitch < 0.0 {
+		Some(pitch.rem_euclid(12.0))
+	} else if pitch >= 128.0 {
+		Some(116.0 + (pitch - 116.0).rem_euclid(12.0))
+	} else {
+		Some(pitch)
+	}
 }
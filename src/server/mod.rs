@@ -1,14 +1,328 @@
-use crate::data::{icp, sheet::*};
+use crate::data::{
+	icp,
+	layout::{Click, Layout, Metronome},
+	sheet::*,
+	synth::Patch,
+};
+use crate::util::Range;
+use derive_more::Display;
+use std::error::Error;
+use std::sync::mpsc::*;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Event {
 	SetTempo(f64),
+	/// The region to loop over instead of the whole sheet
+	SetLoop(Option<Range>),
+	/// The time patterns the metronome follows
+	SetLayout(Layout),
+	SetMetronome(Metronome),
 	PlayStart(Sheet, f64),
 	PlayStop,
 	SheetChanged(Sheet),
 	ICP(icp::Event),
+	SetPatch(Arc<Patch>),
+	/// The files used by the patch, loaded by the server before reaching the audio thread
+	SetResources(Arc<audio::Resources>),
 	Shutdown,
 }
 
+/// Messages sent back to the frontend
+#[derive(Debug)]
+pub enum Notification {
+	/// An output stopped working, the server keeps playing through the others
+	OutputFailed(Output, String),
+	/// The server stopped because of an error
+	Error(String),
+	/// Something went wrong, but the server keeps running
+	Warning(String),
+	/// A message for the user, such as the end of an export
+	Info(String),
+}
+
+#[derive(Debug, Display, Clone, Copy, PartialEq)]
+pub enum Output {
+	#[display(fmt = "Synth")]
+	Synth,
+	#[display(fmt = "MIDI")]
+	Mpe,
+	#[display(fmt = "OSC")]
+	Osc,
+}
+
+/// A sink for the instrument events produced by the transport
+pub trait Backend: Send {
+	/// Handles an event happening `offset` seconds after the start of the current update
+	fn process(&mut self, offset: f64, event: icp::Event) -> Result<(), Box<dyn Error>>;
+
+	/// Plays a metronome click `offset` seconds after the start of the current update
+	fn click(&mut self, _offset: f64, _click: Click) {}
+
+	/// Adds the next audio frame to `frame`, for backends that make sound themselves
+	fn next_frame(&mut self, _frame: &mut [f64]) {}
+
+	/// Changes the sound of the backends that have a synth patch
+	fn set_patch(&mut self, _patch: &Arc<Patch>) {}
+
+	/// Gives the files of the patch to the backends that can play them
+	fn set_resources(&mut self, _resources: &Arc<audio::Resources>) {}
+
+	/// Tells when the events of the coming update are heard, for the backends that hand them to another thread
+	fn start_update(&mut self, _heard_at: Instant) {}
+}
+
+pub mod bin;
+pub use bin::Bin;
+pub mod meters;
+pub use meters::Meters;
+mod playhead;
+pub use playhead::Playhead;
+mod transport;
+pub use transport::Transport;
+
+/// Plays the same transport through any number of backends
+pub struct Engine {
+	pub transport: Transport,
+	backends: Vec<(Output, Box<dyn Backend>)>,
+	playhead: Option<Arc<Playhead>>,
+	to_frontend: Sender<Notification>,
+}
+
+impl Engine {
+	pub fn new(backends: Vec<(Output, Box<dyn Backend>)>, to_frontend: Sender<Notification>) -> Engine {
+		Engine {
+			transport: Transport::new(),
+			backends,
+			playhead: None,
+			to_frontend,
+		}
+	}
+
+	pub fn set_playhead(&mut self, playhead: Arc<Playhead>) {
+		self.playhead = Some(playhead);
+	}
+
+	pub fn process_event(&mut self, event: Event) {
+		match &event {
+			Event::SetPatch(patch) => {
+				for (_, backend) in &mut self.backends {
+					backend.set_patch(patch);
+				}
+			}
+			Event::SetResources(resources) => {
+				for (_, backend) in &mut self.backends {
+					backend.set_resources(resources);
+				}
+			}
+			_ => {}
+		}
+		for &icp in self.transport.process_event(event) {
+			dispatch(&mut self.backends, &self.to_frontend, 0.0, icp);
+		}
+	}
+
+	pub fn start_update(&mut self, heard_at: Instant) {
+		for (_, backend) in &mut self.backends {
+			backend.start_update(heard_at);
+		}
+	}
+
+	/// Plays an event right away, without going through the transport
+	pub fn process_icp(&mut self, event: icp::Event) {
+		dispatch(&mut self.backends, &self.to_frontend, 0.0, event);
+	}
+
+	pub fn update(&mut self, dt: f64) {
+		// the position at the start of the update is the one about to be heard
+		if let Some(playhead) = &self.playhead {
+			playhead.publish(self.transport.cursor, self.transport.active);
+		}
+		let span = self.transport.advance(dt);
+		for &(offset, icp) in &span.events {
+			dispatch(&mut self.backends, &self.to_frontend, offset, icp);
+		}
+		for &(offset, click) in &span.clicks {
+			for (_, backend) in &mut self.backends {
+				backend.click(offset, click);
+			}
+		}
+	}
+
+	pub fn next_frame(&mut self, frame: &mut [f64]) {
+		for x in frame.iter_mut() {
+			*x = 0.0;
+		}
+		for (_, backend) in &mut self.backends {
+			backend.next_frame(frame);
+		}
+	}
+}
+
+// failing backends are dropped, so that the others can keep playing
+fn dispatch(
+	backends: &mut Vec<(Output, Box<dyn Backend>)>,
+	to_frontend: &Sender<Notification>,
+	offset: f64,
+	event: icp::Event,
+) {
+	let mut i = 0;
+	while i < backends.len() {
+		if let Err(err) = backends[i].1.process(offset, event) {
+			let (output, _) = backends.remove(i);
+			to_frontend.send(Notification::OutputFailed(output, err.to_string())).ok();
+		} else {
+			i += 1;
+		}
+	}
+}
+
+/// The outputs to play through, they all follow the same transport
+pub struct Outputs {
+	pub synth: bool,
+	pub audio: AudioConfig,
+	pub mpe: Option<midi::MidiConfig>,
+	pub osc: Option<OscConfig>,
+}
+
+/// How the synth plays through the audio device, `None` uses the default of the system
+#[derive(Debug, Clone, Default)]
+pub struct AudioConfig {
+	pub device: Option<String>,
+	pub sample_rate: Option<u32>,
+	pub buffer_size: Option<u32>,
+}
+
+pub struct Server {
+	sender: Sender<Event>,
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Server {
+	pub fn send(&self, event: Event) {
+		// if the server has stopped, it already notified the frontend about it
+		self.sender.send(event).ok();
+	}
+
+	/// Stops the server, and waits until it is done
+	pub fn shutdown(&mut self) -> thread::Result<()> {
+		self.sender.send(Event::Shutdown).ok();
+		match self.thread.take() {
+			Some(thread) => thread.join(),
+			None => Ok(()),
+		}
+	}
+}
+
+pub fn launch(
+	outputs: Outputs,
+	meters: Arc<Meters>,
+	playhead: Arc<Playhead>,
+	to_frontend: Sender<Notification>,
+) -> Server {
+	let (sender, receiver) = channel();
+	let thread = thread::spawn(move || {
+		if let Err(err) = run(receiver, outputs, meters, playhead, to_frontend.clone()) {
+			to_frontend.send(Notification::Error(err.to_string())).ok();
+		}
+	});
+	Server {
+		sender,
+		thread: Some(thread),
+	}
+}
+
+fn run(
+	receiver: Receiver<Event>,
+	outputs: Outputs,
+	meters: Arc<Meters>,
+	playhead: Arc<Playhead>,
+	to_frontend: Sender<Notification>,
+) -> Result<(), Box<dyn Error>> {
+	// nothing to show unless the synth is playing
+	meters.set_nb_channels(0);
+	playhead.publish(0.0, false);
+
+	// the external outputs are driven from this thread, the audio callback only runs the synth
+	// when the synth plays, its transport is the only one, and it hands the events of the external outputs back here
+	let mut backends: Vec<(Output, Box<dyn Backend>)> = vec![];
+	if let Some(config) = outputs.mpe {
+		match midi::Mpe::new(config, to_frontend.clone()) {
+			Ok(mpe) => backends.push((Output::Mpe, Box::new(mpe))),
+			Err(err) => {
+				to_frontend.send(Notification::OutputFailed(Output::Mpe, err.to_string())).ok();
+			}
+		}
+	}
+	if let Some(config) = &outputs.osc {
+		match osc::Osc::new(config) {
+			Ok(osc) => backends.push((Output::Osc, Box::new(osc))),
+			Err(err) => {
+				to_frontend.send(Notification::OutputFailed(Output::Osc, err.to_string())).ok();
+			}
+		}
+	}
+	let mut stream = None;
+	if outputs.synth {
+		let external = !backends.is_empty();
+		match audio::Stream::new(&outputs.audio, external, meters, playhead.clone(), to_frontend.clone()) {
+			Ok(audio) => stream = Some(audio),
+			Err(err) => {
+				to_frontend.send(Notification::OutputFailed(Output::Synth, err.to_string())).ok();
+			}
+		}
+	}
+	let mut engine = Engine::new(backends, to_frontend);
+	// the playhead follows what is heard, which is the synth when there is one
+	if stream.is_none() {
+		engine.set_playhead(playhead);
+	}
+	run_timed(receiver, engine, stream)
+}
+
+// short enough for the events of the external outputs to stay in time, they are sent in order within an update
+const UPDATE_RATE: f64 = 0.005;
+
+// drives the external outputs with a timer, or sends them what the synth plays when it is heard
+fn run_timed(
+	receiver: Receiver<Event>,
+	mut engine: Engine,
+	mut stream: Option<audio::Stream>,
+) -> Result<(), Box<dyn Error>> {
+	let update_rate = Duration::from_secs_f64(UPDATE_RATE);
+	let mut next_update = Instant::now() + update_rate;
+	loop {
+		// events are handled as soon as they come, so that live notes aren't delayed until the next update
+		match receiver.recv_timeout(next_update.saturating_duration_since(Instant::now())) {
+			Ok(Event::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+			Ok(event) => match &mut stream {
+				Some(stream) => stream.send(event)?,
+				None => engine.process_event(event),
+			},
+			Err(RecvTimeoutError::Timeout) => {}
+		}
+		while Instant::now() >= next_update {
+			match &mut stream {
+				Some(stream) => {
+					stream.empty_bin();
+					stream.for_due_events(Instant::now(), |event| engine.process_icp(event));
+				}
+				None => engine.update(UPDATE_RATE),
+			}
+			next_update += update_rate;
+		}
+	}
+	// the external synths would keep holding the notes otherwise
+	engine.process_icp(icp::Event::NoteStopAll);
+	if let Some(stream) = stream {
+		stream.stop()?;
+	}
+	Ok(())
+}
+
 pub mod audio;
 pub mod midi;
+pub mod osc;
+pub use osc::OscConfig;
@@ -0,0 +1,270 @@
+//! Open Sound Control over UDP, for synths that take exact frequencies such as SuperCollider or Pure Data
+
+use super::Backend;
+use crate::data::icp;
+use std::error::Error;
+use std::net::UdpSocket;
+
+/// Where the messages are sent, and what they look like
+///
+/// A template is an address followed by its arguments, for example `/note/on id freq vel`.
+/// `id`, `freq`, `vel`, `pan`, `pressure` and `timbre` are replaced by the values of the note,
+/// other arguments are sent as they are.
+#[derive(Debug, Clone)]
+pub struct OscConfig {
+	pub target: String,
+	pub note_on: String,
+	pub note_off: String,
+	pub change_freq: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+	Id,
+	Freq,
+	Velocity,
+	Pan,
+	Pressure,
+	Timbre,
+	Int(i32),
+	Float(f32),
+	Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Template {
+	address: String,
+	args: Vec<Arg>,
+}
+
+impl Template {
+	fn parse(template: &str) -> Result<Template, String> {
+		let mut tokens = template.split_whitespace();
+		let address = match tokens.next() {
+			Some(address) if address.starts_with('/') => address.to_string(),
+			_ => return Err(format!("the OSC message `{}` doesn't start with an address", template)),
+		};
+		let args = tokens
+			.map(|token| match token {
+				"id" => Arg::Id,
+				"freq" => Arg::Freq,
+				"vel" => Arg::Velocity,
+				"pan" => Arg::Pan,
+				"pressure" => Arg::Pressure,
+				"timbre" => Arg::Timbre,
+				_ => {
+					if let Ok(x) = token.parse() {
+						Arg::Int(x)
+					} else if let Ok(x) = token.parse() {
+						Arg::Float(x)
+					} else {
+						Arg::Str(token.to_string())
+					}
+				}
+			})
+			.collect();
+		Ok(Template { address, args })
+	}
+
+	/// Writes the message into `packet`, which keeps its capacity from one message to the next
+	fn encode(&self, packet: &mut Vec<u8>, note: &Playing) {
+		packet.clear();
+		push_padded(packet, self.address.as_bytes());
+		packet.push(b',');
+		for arg in &self.args {
+			packet.push(match arg {
+				Arg::Id | Arg::Int(_) => b'i',
+				Arg::Freq | Arg::Velocity | Arg::Pan | Arg::Pressure | Arg::Timbre | Arg::Float(_) => b'f',
+				Arg::Str(_) => b's',
+			});
+		}
+		push_padded(packet, &[]);
+		for arg in &self.args {
+			match arg {
+				Arg::Id => packet.extend(&(note.id as i32).to_be_bytes()),
+				Arg::Freq => packet.extend(&(note.freq as f32).to_be_bytes()),
+				// the notes of the sheet don't have a velocity yet
+				Arg::Velocity => packet.extend(&1f32.to_be_bytes()),
+				Arg::Pan => packet.extend(&(note.pan as f32).to_be_bytes()),
+				Arg::Pressure => packet.extend(&(note.pressure as f32).to_be_bytes()),
+				Arg::Timbre => packet.extend(&(note.timbre as f32).to_be_bytes()),
+				Arg::Int(x) => packet.extend(&x.to_be_bytes()),
+				Arg::Float(x) => packet.extend(&x.to_be_bytes()),
+				Arg::Str(x) => push_padded(packet, x.as_bytes()),
+			}
+		}
+	}
+}
+
+// strings end with at least one zero, and everything is aligned to 4 bytes
+fn push_padded(packet: &mut Vec<u8>, bytes: &[u8]) {
+	packet.extend(bytes);
+	packet.push(0);
+	while packet.len() % 4 != 0 {
+		packet.push(0);
+	}
+}
+
+/// The values of each playing note, for the messages that need them
+#[derive(Clone, Copy)]
+struct Playing {
+	id: icp::NoteId,
+	freq: f64,
+	pan: f64,
+	pressure: f64,
+	timbre: f64,
+}
+
+pub struct Osc {
+	socket: UdpSocket,
+	note_on: Template,
+	note_off: Template,
+	change_freq: Template,
+	playing: Vec<Playing>,
+	packet: Vec<u8>,
+}
+
+impl Backend for Osc {
+	fn process(&mut self, _offset: f64, event: icp::Event) -> Result<(), Box<dyn Error>> {
+		self.process_icp(event)
+	}
+}
+
+impl Osc {
+	pub fn new(config: &OscConfig) -> Result<Osc, Box<dyn Error>> {
+		let socket = UdpSocket::bind("0.0.0.0:0")?;
+		socket.connect(&config.target)?;
+		// the timed thread also feeds the synth, it must never wait on the network
+		socket.set_nonblocking(true)?;
+		Ok(Osc {
+			socket,
+			note_on: Template::parse(&config.note_on)?,
+			note_off: Template::parse(&config.note_off)?,
+			change_freq: Template::parse(&config.change_freq)?,
+			playing: Vec::with_capacity(256),
+			packet: Vec::with_capacity(256),
+		})
+	}
+
+	fn process_icp(&mut self, event: icp::Event) -> Result<(), Box<dyn Error>> {
+		match event {
+			icp::Event::NotePlay(note) => {
+				let note = Playing {
+					id: note.id,
+					freq: note.freq,
+					pan: note.pan,
+					pressure: note.pressure,
+					timbre: note.timbre,
+				};
+				self.playing.push(note);
+				send(&self.socket, &mut self.packet, &self.note_on, note)?;
+			}
+			icp::Event::NoteStop(id) => {
+				if let Some(i) = self.playing.iter().position(|x| x.id == id) {
+					let note = self.playing.swap_remove(i);
+					send(&self.socket, &mut self.packet, &self.note_off, note)?;
+				}
+			}
+			icp::Event::NoteStopAll => {
+				for note in self.playing.drain(..) {
+					send(&self.socket, &mut self.packet, &self.note_off, note)?;
+				}
+			}
+			icp::Event::NoteChangeFreq(id, freq) => {
+				if let Some(note) = self.playing.iter_mut().find(|x| x.id == id) {
+					note.freq = freq;
+					send(&self.socket, &mut self.packet, &self.change_freq, *note)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+fn send(socket: &UdpSocket, packet: &mut Vec<u8>, template: &Template, note: Playing) -> Result<(), Box<dyn Error>> {
+	template.encode(packet, &note);
+	match socket.send(packet) {
+		// a full buffer drops the message, like a lost packet
+		Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+		// nothing is listening on the other side yet
+		Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => Ok(()),
+		result => result.map(|_| ()).map_err(|err| err.into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn note() -> Playing {
+		Playing {
+			id: 7,
+			freq: 440.0,
+			pan: -1.0,
+			pressure: 0.25,
+			timbre: 0.5,
+		}
+	}
+
+	fn encode(template: &str) -> Vec<u8> {
+		let mut packet = Vec::new();
+		Template::parse(template).unwrap().encode(&mut packet, &note());
+		packet
+	}
+
+	#[test]
+	fn parses_the_arguments() {
+		let template = Template::parse("/note/on id freq vel 3 0.5 x").unwrap();
+		assert_eq!(template.address, "/note/on");
+		assert_eq!(
+			template.args,
+			vec![Arg::Id, Arg::Freq, Arg::Velocity, Arg::Int(3), Arg::Float(0.5), Arg::Str("x".to_string())]
+		);
+	}
+
+	#[test]
+	fn needs_an_address() {
+		assert!(Template::parse("note id").is_err());
+		assert!(Template::parse("").is_err());
+	}
+
+	#[test]
+	fn pads_to_4_bytes() {
+		// the zero ending a string takes a whole word when the string already fills the last one
+		assert_eq!(encode("/off"), b"/off\0\0\0\0,\0\0\0");
+		assert_eq!(encode("/n"), b"/n\0\0,\0\0\0");
+		let packet = encode("/n abcde");
+		assert_eq!(packet, b"/n\0\0,s\0\0abcde\0\0\0");
+		assert_eq!(packet.len() % 4, 0);
+	}
+
+	#[test]
+	fn writes_the_type_tags() {
+		let packet = encode("/n id freq vel pan pressure timbre 1 2.5 s");
+		assert_eq!(&packet[4..16], b",ifffffifs\0\0");
+	}
+
+	#[test]
+	fn writes_big_endian_arguments() {
+		let packet = encode("/n id freq pan pressure timbre -2 2.5");
+		// after the address and the 7 type tags, each padded to 4 bytes
+		let args = &packet[16..];
+		assert_eq!(&args[0..4], &[0, 0, 0, 7]);
+		assert_eq!(&args[4..8], &440f32.to_be_bytes());
+		assert_eq!(&args[4..8], &[0x43, 0xdc, 0, 0]);
+		assert_eq!(&args[8..12], &(-1f32).to_be_bytes());
+		assert_eq!(&args[12..16], &0.25f32.to_be_bytes());
+		assert_eq!(&args[16..20], &0.5f32.to_be_bytes());
+		assert_eq!(&args[20..24], &[0xff, 0xff, 0xff, 0xfe]);
+		assert_eq!(&args[24..28], &2.5f32.to_be_bytes());
+		assert_eq!(args.len(), 28);
+	}
+
+	#[test]
+	fn reuses_the_packet() {
+		let mut packet = Vec::new();
+		Template::parse("/a id").unwrap().encode(&mut packet, &note());
+		Template::parse("/b").unwrap().encode(&mut packet, &note());
+		assert_eq!(packet, b"/b\0\0,\0\0\0");
+	}
+}
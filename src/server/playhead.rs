@@ -0,0 +1,25 @@
+//! Position of the transport, written by the server without locking and read by the cursor
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+#[derive(Default)]
+pub struct Playhead {
+	position: AtomicU64, // bits of a f64, in beats
+	playing: AtomicBool,
+}
+
+impl Playhead {
+	pub fn publish(&self, position: f64, playing: bool) {
+		self.position.store(position.to_bits(), Ordering::Relaxed);
+		self.playing.store(playing, Ordering::Relaxed);
+	}
+
+	/// Returns the position, if the transport is playing
+	pub fn read(&self) -> Option<f64> {
+		if self.playing.load(Ordering::Relaxed) {
+			Some(f64::from_bits(self.position.load(Ordering::Relaxed)))
+		} else {
+			None
+		}
+	}
+}
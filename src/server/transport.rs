@@ -0,0 +1,293 @@
+//! Playback position and event generation, shared by every backend
+
+use super::{bin::Garbage, Bin, Event};
+use crate::data::{
+	icp,
+	layout::{Click, Layout, Metronome},
+	sheet::*,
+};
+use crate::util::*;
+use std::cmp::Ordering;
+
+// enough for dense sheets, more only cost an allocation
+const SPAN_CAPACITY: usize = 1024;
+
+/// What happens during an update, with offsets in seconds from its start
+#[derive(Default)]
+pub struct Span {
+	pub events: Vec<(f64, icp::Event)>,
+	pub clicks: Vec<(f64, Click)>,
+}
+
+pub struct Transport {
+	pub sheet: Sheet,
+	pub cursor: f64,
+	pub active: bool,
+	pub tempo: f64,
+	pub looping: bool,
+	pub region: Option<Range>, // looped over instead of the whole sheet
+	pub layout: Layout,
+	pub metronome: Metronome,
+	// filled again at each call, so that the audio thread doesn't allocate
+	span: Span,
+	events: Vec<icp::Event>,
+	bin: Bin, // the sheets and layouts that are replaced
+}
+
+impl Default for Transport {
+	fn default() -> Transport {
+		Transport::new()
+	}
+}
+
+impl Transport {
+	pub fn new() -> Transport {
+		Transport {
+			sheet: Sheet::default(),
+			cursor: 0.0,
+			active: false,
+			tempo: 140.0,
+			looping: true,
+			region: None,
+			layout: Layout::default(),
+			metronome: Metronome::default(),
+			span: Span {
+				events: Vec::with_capacity(SPAN_CAPACITY),
+				clicks: Vec::with_capacity(SPAN_CAPACITY),
+			},
+			events: Vec::with_capacity(SPAN_CAPACITY),
+			bin: Bin::default(),
+		}
+	}
+
+	pub fn set_bin(&mut self, bin: Bin) {
+		self.bin = bin;
+	}
+
+	// returns the instrument events caused by a frontend event
+	pub fn process_event(&mut self, event: Event) -> &[icp::Event] {
+		self.events.clear();
+		match event {
+			Event::SetTempo(tempo) => self.tempo = tempo,
+			Event::SetLoop(region) => self.region = region,
+			Event::SetLayout(mut layout) => {
+				layout.sort_markers();
+				let layout = std::mem::replace(&mut self.layout, layout);
+				self.bin.throw(Garbage::Layout(layout));
+			}
+			Event::SetMetronome(metronome) => self.metronome = metronome,
+			Event::PlayStart(sheet, cursor) => {
+				self.cursor = cursor;
+				self.replace_sheet(sheet);
+				self.active = true;
+				let events = &mut self.events;
+				self.sheet.for_notes_at_time(cursor, |note| events.push(icp::Event::NotePlay(note)));
+			}
+			Event::PlayStop => {
+				self.active = false;
+				self.events.push(icp::Event::NoteStopAll);
+			}
+			Event::SheetChanged(sheet) => self.replace_sheet(sheet),
+			Event::ICP(icp) => self.events.push(icp),
+			Event::SetPatch(_) | Event::SetResources(_) | Event::Shutdown => {}
+		}
+		&self.events
+	}
+
+	fn replace_sheet(&mut self, sheet: Sheet) {
+		let sheet = std::mem::replace(&mut self.sheet, sheet);
+		self.bin.throw(Garbage::Sheet(sheet));
+	}
+
+	// moves the cursor forward by `dt` seconds, and returns what happens in that span
+	pub fn advance(&mut self, dt: f64) -> &Span {
+		// the buffers are taken out of the transport while it is borrowed to fill them
+		let mut span = std::mem::take(&mut self.span);
+		span.events.clear();
+		span.clicks.clear();
+		if self.active {
+			self.fill_span(&mut span, dt);
+		}
+		self.span = span;
+		&self.span
+	}
+
+	fn fill_span(&mut self, span: &mut Span, dt: f64) {
+		let beats_per_sec = self.tempo / 60.0;
+		let mut start = self.cursor;
+		let mut length = dt * beats_per_sec;
+		let mut offset = 0.0; // in beats from the start of the span
+		loop {
+			let Range(loop_start, loop_end) = self.loop_range(start);
+			let end = start + length;
+			// a span ending right at the end of the loop wraps, so that the notes ending there are stopped
+			if !self.looping || loop_end <= loop_start || end < loop_end {
+				self.extend_span(span, start, end, offset);
+				self.cursor = end;
+				if !self.looping && end > self.sheet.get_size() {
+					self.active = false;
+				}
+				break;
+			}
+			if start >= loop_end {
+				// past the end of the sheet, such as after notes were removed
+				start = loop_start;
+				continue;
+			}
+			self.extend_span(span, start, loop_end, offset);
+			offset += loop_end - start;
+			length -= loop_end - start;
+			// the notes crossing the boundaries are cut at the end, and played again from the start
+			// the ones ending right at the end are stopped here as well, as the span leaves its end out
+			let events = &mut span.events;
+			self.sheet.for_notes_before_time(loop_end, |note| events.push((offset, icp::Event::NoteStop(note.id))));
+			self.sheet.for_notes_at_time(loop_start, |note| events.push((offset, icp::Event::NotePlay(note))));
+			start = loop_start;
+		}
+		for (time, _) in &mut span.events {
+			*time /= beats_per_sec;
+		}
+		for (time, _) in &mut span.clicks {
+			*time /= beats_per_sec;
+		}
+		// the external outputs play the events in the order they get them
+		span.events.sort_unstable_by(|(a, x), (b, y)| {
+			a.partial_cmp(b).unwrap_or(Ordering::Equal).then(order(x).cmp(&order(y)))
+		});
+	}
+
+	// adds what happens from `start` to `end` in beats, `offset` beats after the start of the span
+	fn extend_span(&self, span: &mut Span, start: f64, end: f64, offset: f64) {
+		// the events right at the end happen in the next span
+		let events = &mut span.events;
+		self.sheet.for_events(Range(start, end), |time, event| events.push((offset + time - start, event)));
+		if self.metronome.enabled {
+			let first = span.clicks.len();
+			self.layout.get_clicks(start, end, self.metronome.subdivisions, &mut span.clicks);
+			for (time, _) in &mut span.clicks[first..] {
+				*time = offset + *time - start;
+			}
+		}
+	}
+
+	// the loop region only applies once the cursor is before its end
+	fn loop_range(&self, position: f64) -> Range {
+		match self.region {
+			Some(region) if position < region.1 => region,
+			_ => Range(0.0, self.sheet.get_size()),
+		}
+	}
+}
+
+// at the same time, notes are stopped before others start, which frees their MIDI channels
+fn order(event: &icp::Event) -> u8 {
+	match event {
+		icp::Event::NoteStop(_) | icp::Event::NoteStopAll => 0,
+		icp::Event::NoteChangeFreq(..) => 1,
+		icp::Event::NotePlay(_) => 2,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use druid::Point;
+
+	fn add_note(sheet: &mut Sheet, start: f64, end: f64) -> icp::NoteId {
+		sheet.add_note(Note::new(Point::new(start, 440f64.log2()), end - start)).into_raw_parts().0
+	}
+
+	// one beat per second, so that the offsets are in beats as well
+	fn play(sheet: Sheet) -> Transport {
+		let mut transport = Transport::new();
+		transport.process_event(Event::SetTempo(60.0));
+		transport.process_event(Event::PlayStart(sheet, 0.0));
+		transport
+	}
+
+	fn stops(span: &Span, id: icp::NoteId) -> Vec<f64> {
+		span.events
+			.iter()
+			.filter(|(_, event)| matches!(event, icp::Event::NoteStop(x) if *x == id))
+			.map(|&(time, _)| time)
+			.collect()
+	}
+
+	fn plays(span: &Span, id: icp::NoteId) -> Vec<f64> {
+		span.events
+			.iter()
+			.filter(|(_, event)| matches!(event, icp::Event::NotePlay(note) if note.id == id))
+			.map(|&(time, _)| time)
+			.collect()
+	}
+
+	#[test]
+	fn wraps_at_the_loop_boundary() {
+		let mut sheet = Sheet::default();
+		let first = add_note(&mut sheet, 0.0, 1.0);
+		let last = add_note(&mut sheet, 1.0, 2.0);
+		let mut transport = play(sheet);
+
+		transport.advance(1.5);
+		let span = transport.advance(1.0);
+		assert_eq!(stops(span, last), vec![0.5]);
+		assert_eq!(plays(span, first), vec![0.5]);
+		assert_eq!(transport.cursor, 0.5);
+		assert!(transport.active);
+	}
+
+	#[test]
+	fn wraps_when_the_span_ends_on_the_loop_end() {
+		let mut sheet = Sheet::default();
+		let note = add_note(&mut sheet, 0.0, 2.0);
+		let mut transport = play(sheet);
+
+		let span = transport.advance(2.0);
+		assert_eq!(plays(span, note), vec![0.0]);
+		assert_eq!(stops(span, note), vec![2.0]);
+		assert_eq!(transport.cursor, 0.0);
+		let span = transport.advance(0.5);
+		assert_eq!(plays(span, note), vec![0.0]);
+	}
+
+	#[test]
+	fn stops_once_on_a_span_boundary() {
+		let mut sheet = Sheet::default();
+		let note = add_note(&mut sheet, 0.0, 1.0);
+		add_note(&mut sheet, 0.0, 4.0);
+		let mut transport = play(sheet);
+
+		let span = transport.advance(1.0);
+		assert_eq!(plays(span, note), vec![0.0]);
+		assert!(stops(span, note).is_empty());
+		let span = transport.advance(1.0);
+		assert_eq!(stops(span, note), vec![0.0]);
+	}
+
+	#[test]
+	fn stops_at_the_end_without_looping() {
+		let mut sheet = Sheet::default();
+		let note = add_note(&mut sheet, 0.0, 1.0);
+		let mut transport = play(sheet);
+		transport.looping = false;
+
+		let span = transport.advance(2.0);
+		assert_eq!(stops(span, note), vec![1.0]);
+		assert!(!transport.active);
+	}
+
+	#[test]
+	fn events_are_in_order() {
+		let mut sheet = Sheet::default();
+		add_note(&mut sheet, 1.0, 2.0);
+		add_note(&mut sheet, 0.0, 1.0);
+		add_note(&mut sheet, 0.5, 3.0);
+		let mut transport = play(sheet);
+
+		let span = transport.advance(2.5);
+		let times: Vec<f64> = span.events.iter().map(|&(time, _)| time).collect();
+		assert_eq!(times, vec![0.0, 0.5, 1.0, 1.0, 2.0]);
+		// the note ending at 1 is stopped before the next one starts
+		assert!(matches!(span.events[2].1, icp::Event::NoteStop(_)));
+	}
+}
@@ -0,0 +1,37 @@
+//! User preferences, kept across sessions in the config directory
+//!
+//! The settings are saved as they are, missing fields are filled with their default value when loading.
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use super::editors::settings;
+
+fn path() -> Option<PathBuf> {
+	dirs::config_dir().map(|dir| dir.join("harmoxen").join("config.ron"))
+}
+
+/// Reads the saved settings, along with what was wrong with them when the defaults are used instead
+pub fn load() -> (settings::State, Option<String>) {
+	let settings = path()
+		.and_then(|path| fs::read_to_string(path).ok())
+		.map(|data| ron::from_str::<settings::State>(&data));
+	match settings {
+		Some(Ok(settings)) => (settings, None),
+		Some(Err(err)) => (
+			settings::State::default(),
+			Some(format!("The config file is invalid, using defaults: {}", err)),
+		),
+		None => (settings::State::default(), None),
+	}
+}
+
+pub fn save(settings: &settings::State) -> Result<(), Box<dyn Error>> {
+	let path = path().ok_or("no config directory available")?;
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	fs::write(path, ron::ser::to_string_pretty(settings, Default::default())?)?;
+	Ok(())
+}
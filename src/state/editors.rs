@@ -7,9 +7,11 @@ pub struct State {
 	pub sheet_editor: sheet_editor::State,
 	pub layout_editor: layout_editor::State,
 	pub settings: settings::State,
+	pub patch: Patch,
 }
 
 use crate::commands as cmds;
+use crate::data::synth::Patch;
 use druid::{Data, DelegateCtx, Lens};
 
 impl State {
@@ -28,13 +30,6 @@ impl State {
 	}
 
 	pub fn apply_settings(&mut self, ctx: &mut DelegateCtx) {
-		match self.settings.backend {
-			settings::Backend::Audio => {
-				ctx.submit_command(cmds::BACKEND_SET_AUDIO);
-			}
-			settings::Backend::MPE { port } => {
-				ctx.submit_command(cmds::BACKEND_SET_MPE.with(port));
-			}
-		}
+		ctx.submit_command(cmds::BACKEND_SET_OUTPUTS);
 	}
 }
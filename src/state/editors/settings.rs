@@ -1,22 +1,193 @@
 use derive_more::Display;
 use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 
-#[derive(Clone, Data, Lens, Default)]
+/// Every enabled output plays at the same time, the settings are saved as they are in the config
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
-	pub backend: Backend,
-	pub mpe_port_names: Rc<Vec<String>>,
+	pub synth: bool,
+	pub audio: AudioOutput,
+	pub mpe: MidiOutput,
+	pub osc: OscOutput,
+	pub midi_input: MidiInput,
+	pub return_to_start: bool, // the cursor goes back to where playback started when stopping
 }
+impl Default for State {
+	fn default() -> State {
+		State {
+			synth: true,
+			audio: AudioOutput::default(),
+			mpe: MidiOutput::default(),
+			osc: OscOutput::default(),
+			midi_input: MidiInput::default(),
+			return_to_start: true,
+		}
+	}
+}
+
+impl State {
+	/// The name of the chosen audio device, `None` for the default one
+	pub fn audio_device(&self) -> Option<String> {
+		self.audio.device.name().filter(|_| self.audio.device.index > 0).cloned()
+	}
+}
+
+/// An item of a list given by the system, saved by name since the list changes when devices are plugged in or out
+#[derive(Clone, Data, Lens, Default, Serialize, Deserialize)]
+#[serde(from = "Option<String>", into = "Option<String>")]
+pub struct Choice {
+	pub index: usize,
+	pub names: Rc<Vec<String>>,
+}
+
+impl Choice {
+	pub fn name(&self) -> Option<&String> {
+		self.names.get(self.index)
+	}
+
+	/// Keeps the same item selected when it is still in the new list, returns whether it is
+	pub fn set_names(&mut self, names: Vec<String>) -> bool {
+		let found = self.name().and_then(|name| names.iter().position(|x| x == name));
+		self.index = found.unwrap_or(0);
+		self.names = Rc::new(names);
+		found.is_some()
+	}
+}
+
+// the list isn't known yet when loading, the saved item is found once it is set
+impl From<Option<String>> for Choice {
+	fn from(name: Option<String>) -> Choice {
+		Choice {
+			index: 0,
+			names: Rc::new(name.into_iter().collect()),
+		}
+	}
+}
+
+impl From<Choice> for Option<String> {
+	fn from(choice: Choice) -> Option<String> {
+		choice.name().cloned()
+	}
+}
+
+/// The first audio device stands for the default of the system
+pub const DEFAULT_DEVICE: &str = "Default device";
+
+pub const SAMPLE_RATES: [u32; 4] = [44100, 48000, 88200, 96000];
+pub const BUFFER_SIZES: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
 
-#[derive(Clone, Data, Display)]
-pub enum Backend {
-	#[display(fmt = "Synth")]
-	Audio,
+/// Each rate and size is an index where 0 stands for the default of the system, and the others are shifted by one
+#[derive(Clone, Data, Lens, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioOutput {
+	pub device: Choice,
+	pub sample_rate: usize,
+	pub buffer_size: usize,
+}
+
+impl AudioOutput {
+	pub fn sample_rate(&self) -> Option<u32> {
+		self.sample_rate.checked_sub(1).and_then(|i| SAMPLE_RATES.get(i).copied())
+	}
+
+	pub fn buffer_size(&self) -> Option<u32> {
+		self.buffer_size.checked_sub(1).and_then(|i| BUFFER_SIZES.get(i).copied())
+	}
+}
+
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiOutput {
+	pub enabled: bool,
+	pub port: Choice,
+	pub mode: MidiMode,
+	pub first_channel: u8, // from 1 to 16, the channels notes are rotated on without MPE
+	pub last_channel: u8,
+	pub bend_range: f64, // in semitones, as set on the synth
+	pub stealing: bool,  // the oldest note is cut when all the channels are playing
+}
+impl Default for MidiOutput {
+	fn default() -> MidiOutput {
+		MidiOutput {
+			enabled: false,
+			port: Choice::default(),
+			mode: MidiMode::Mpe,
+			first_channel: 1,
+			last_channel: 16,
+			bend_range: 2.0,
+			stealing: true,
+		}
+	}
+}
+
+/// How notes are spread over the channels of the MIDI output, each one having its own pitch bend
+#[derive(Debug, Clone, Copy, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum MidiMode {
+	/// An MPE zone on channels 2 to 16, set up on the synth
 	#[display(fmt = "MPE")]
-	MPE { port: usize },
+	Mpe,
+	/// One note per channel, for multitimbral synths that don't know about MPE
+	#[display(fmt = "Channel rotation")]
+	Rotation,
+}
+
+/// The messages are templates, see `server::OscConfig`
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscOutput {
+	pub enabled: bool,
+	pub target: String,
+	pub note_on: String,
+	pub note_off: String,
+	pub change_freq: String,
 }
-impl Default for Backend {
-	fn default() -> Backend {
-		Backend::Audio
+impl Default for OscOutput {
+	fn default() -> OscOutput {
+		OscOutput {
+			enabled: false,
+			target: "127.0.0.1:57120".into(),
+			note_on: "/note/on id freq vel".into(),
+			note_off: "/note/off id".into(),
+			change_freq: "/note/freq id freq".into(),
+		}
 	}
 }
+
+/// A controller used to record notes, and to play the synth
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiInput {
+	pub enabled: bool,
+	pub port: Choice,
+	pub mapping: KeyMapping,
+	pub reference_key: u8, // the key playing the base of the scale with the layout mapping
+	pub bend_range: f64,   // in semitones, as set on the controller
+	pub quantize: bool,    // recorded notes are snapped to the layout
+	pub monitor: bool,     // the keys are heard through the outputs
+}
+impl Default for MidiInput {
+	fn default() -> MidiInput {
+		MidiInput {
+			enabled: false,
+			port: Choice::default(),
+			mapping: KeyMapping::Tet,
+			reference_key: 60,
+			bend_range: 2.0,
+			quantize: false,
+			monitor: true,
+		}
+	}
+}
+
+/// How the keys of a controller are turned into frequencies
+#[derive(Debug, Clone, Copy, Data, Display, PartialEq, Serialize, Deserialize)]
+pub enum KeyMapping {
+	/// Equal temperament from A4 at 440 Hz, for controllers that bend their notes
+	#[display(fmt = "12-TET")]
+	Tet,
+	/// Each key is a degree of the scale of the layout at the cursor, from the base of the scale at the reference key
+	#[display(fmt = "Layout degrees")]
+	Layout,
+}
@@ -1,11 +1,12 @@
 use crate::data::{
-	layout::Layout,
-	sheet::{Clipboard, Interval, Sheet},
+	layout::{Layout, Metronome},
+	sheet::{Clipboard, Interval, LoopRegion, Sheet},
 };
+use crate::server::Playhead;
 use crate::util::{Frame, Frame2, Range};
 use druid::{Data, Lens};
 use generational_arena::Index;
-use std::{cell::RefCell, collections::HashSet, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc, sync::Arc};
 
 #[derive(Clone, Data, Lens)]
 pub struct State {
@@ -13,6 +14,12 @@ pub struct State {
 	pub sheet: Rc<RefCell<Sheet>>,
 	pub cursor: f64,
 	pub playing: bool,
+	pub recording: bool,         // notes played on the MIDI input or the keyboard are added to the sheet
+	pub keyboard: bool,          // the letter keys play notes
+	pub hex_layout: HexLayout,
+	pub playhead: Arc<Playhead>, // where the server is playing, which the cursor follows
+	pub loop_region: LoopRegion,
+	pub metronome: Metronome,
 	pub layout: Rc<RefCell<Layout>>,
 	pub tempo: f64,
 	pub interval_input: Interval,
@@ -36,6 +43,12 @@ impl Default for State {
 			sheet: Rc::new(RefCell::new(Sheet::default())),
 			cursor: 0.0,
 			playing: false,
+			recording: false,
+			keyboard: false,
+			hex_layout: HexLayout::default(),
+			playhead: Arc::new(Playhead::default()),
+			loop_region: LoopRegion::default(),
+			metronome: Metronome::default(),
 			layout: Rc::new(RefCell::new(Layout::default())),
 			tempo: 172.0,
 			interval_input: Interval::Ratio(3, 2),
@@ -45,3 +58,30 @@ impl Default for State {
 		}
 	}
 }
+
+impl State {
+	/// The end of a note recorded from `start` until playback reached `position`, which may have wrapped around since
+	pub fn recorded_end(&self, start: f64, position: f64) -> f64 {
+		if position > start {
+			return position;
+		}
+		// like the transport, the loop region only applies if the note started before its end
+		match self.loop_region.range() {
+			Some(Range(_, end)) if start < end => end,
+			_ => self.sheet.borrow().get_size(),
+		}
+	}
+}
+
+/// The degrees of the scale between neighbouring keys of the hex keyboard
+#[derive(Clone, Copy, Data, Lens, PartialEq)]
+pub struct HexLayout {
+	pub right: i64,
+	pub up_right: i64,
+}
+impl Default for HexLayout {
+	// the Wicki-Hayden layout in 12-EDO
+	fn default() -> HexLayout {
+		HexLayout { right: 2, up_right: 7 }
+	}
+}
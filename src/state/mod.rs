@@ -1,12 +1,17 @@
 use druid::{Data, Lens};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::{cell::RefCell, rc::Rc};
 
+use crate::server;
+
+pub mod config;
 pub mod editors;
 pub mod history;
 pub use history::History;
 pub mod project;
 pub use project::Project;
+pub mod recovery;
 
 #[derive(Clone, Data, Lens)]
 pub struct State {
@@ -15,6 +20,8 @@ pub struct State {
 	pub history: Rc<RefCell<history::History>>,
 	pub save_path: Option<Rc<PathBuf>>,
 	pub up_to_date: bool,
+	pub meters: Arc<server::Meters>,
+	pub status: String, // the last warning or message, shown in the toolbar for a while
 }
 
 impl State {
@@ -27,6 +34,8 @@ impl State {
 			history: Rc::new(RefCell::new(History::new(project))),
 			save_path: None,
 			up_to_date: true,
+			meters: Arc::new(server::Meters::default()),
+			status: String::new(),
 		}
 	}
 }
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::data::layout::Layout;
-use crate::data::sheet::Sheet;
+use crate::data::layout::{Layout, Metronome};
+use crate::data::sheet::{LoopRegion, Sheet};
+use crate::data::synth::Patch;
 use crate::state::editors::State;
 use generational_arena::Index;
 use std::{cell::RefCell, collections::HashSet, rc::Rc};
@@ -12,6 +13,12 @@ pub struct Project {
 	pub layout: Layout,
 	pub selection: HashSet<Index>,
 	pub tempo: f64,
+	#[serde(default)]
+	pub patch: Patch,
+	#[serde(default)]
+	pub loop_region: LoopRegion,
+	#[serde(default)]
+	pub metronome: Metronome,
 }
 
 impl Project {
@@ -20,11 +27,17 @@ impl Project {
 		let sheet = (*editors.sheet_editor.sheet.borrow()).clone();
 		let selection = (*editors.sheet_editor.selection.borrow()).clone();
 		let tempo = editors.sheet_editor.tempo;
+		let patch = editors.patch.clone();
+		let loop_region = editors.sheet_editor.loop_region;
+		let metronome = editors.sheet_editor.metronome;
 		Project {
 			sheet,
 			layout,
 			selection,
 			tempo,
+			patch,
+			loop_region,
+			metronome,
 		}
 	}
 
@@ -33,5 +46,8 @@ impl Project {
 		editors.sheet_editor.sheet = Rc::new(RefCell::new(self.sheet));
 		editors.sheet_editor.selection = Rc::new(RefCell::new(self.selection));
 		editors.sheet_editor.tempo = self.tempo;
+		editors.patch = self.patch;
+		editors.sheet_editor.loop_region = self.loop_region;
+		editors.sheet_editor.metronome = self.metronome;
 	}
 }
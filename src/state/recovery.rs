@@ -0,0 +1,156 @@
+//! Periodic backups of the open project, used to recover from an unclean shutdown
+//!
+//! Each running instance has its own lock and backup, named after its process id.
+//! The lock file stays locked while the instance runs, and the system releases it when the process ends, even after a crash.
+//! A lock that can be taken belongs to an instance that is gone.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::Project;
+
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+const LOCK_EXTENSION: &str = "lock";
+const BACKUP_EXTENSION: &str = "hxp";
+const RECOVERED_PREFIX: &str = "recovered-";
+
+// held for the whole session
+static LOCK: Mutex<Option<File>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+	pub save_path: Option<PathBuf>,
+	pub project: Project,
+}
+
+fn dir() -> Option<PathBuf> {
+	dirs::data_local_dir().map(|dir| dir.join("harmoxen").join("recovery"))
+}
+
+fn session_file(dir: &Path, session: &str, extension: &str) -> PathBuf {
+	dir.join(session).with_extension(extension)
+}
+
+fn own_session() -> String {
+	std::process::id().to_string()
+}
+
+// the backup of a crashed instance, once the instance `session` took it over
+fn recovered_file(dir: &Path, session: &str) -> PathBuf {
+	dir.join(format!("{}{}", RECOVERED_PREFIX, session)).with_extension(BACKUP_EXTENSION)
+}
+
+// a lock that can't be checked is taken as alive, so that the backup of a running instance is never taken
+fn is_alive(lock: &Path) -> bool {
+	let file = match OpenOptions::new().read(true).write(true).open(lock) {
+		Ok(file) => file,
+		Err(_) => return false,
+	};
+	file.try_lock().is_err()
+}
+
+/// Marks the session as running, and returns true if an instance that is gone left a backup behind
+pub fn start_session() -> bool {
+	let dir = match dir() {
+		Some(dir) => dir,
+		None => return false,
+	};
+	if fs::create_dir_all(&dir).is_err() {
+		return false;
+	}
+	// the crashed instances are looked for before taking the lock, in case this one got the same process id
+	let recovered = find_crashed(&dir);
+	let own = own_session();
+	let lock = File::create(session_file(&dir, &own, LOCK_EXTENSION));
+	if let Ok(lock) = lock {
+		if lock.try_lock().is_ok() {
+			*LOCK.lock().unwrap() = Some(lock);
+		}
+	}
+	recovered
+}
+
+// takes over the first backup left by an instance that is gone, along with the ones it recovered but didn't answer for
+fn find_crashed(dir: &Path) -> bool {
+	let entries = match fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(_) => return false,
+	};
+	let target = recovered_file(dir, &own_session());
+	for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+		let extension = path.extension().and_then(|x| x.to_str());
+		let name = match path.file_stem().and_then(|x| x.to_str()) {
+			Some(name) => name,
+			None => continue,
+		};
+		let (session, backup) = match extension {
+			Some(LOCK_EXTENSION) => (name, path.with_extension(BACKUP_EXTENSION)),
+			Some(BACKUP_EXTENSION) if name.starts_with(RECOVERED_PREFIX) => (&name[RECOVERED_PREFIX.len()..], path.clone()),
+			_ => continue,
+		};
+		let lock = session_file(dir, session, LOCK_EXTENSION);
+		if is_alive(&lock) {
+			continue;
+		}
+		// the rename fails if another instance starting at the same time took the backup first
+		let recovered = backup.exists() && fs::rename(&backup, &target).is_ok();
+		if !session_file(dir, session, BACKUP_EXTENSION).exists() {
+			fs::remove_file(&lock).ok();
+		}
+		if recovered {
+			return true;
+		}
+	}
+	false
+}
+
+/// Removes the session marker along with the backup, nothing needs recovering after this
+pub fn end_session() {
+	if let Some(dir) = dir() {
+		let own = own_session();
+		fs::remove_file(session_file(&dir, &own, BACKUP_EXTENSION)).ok();
+		// some systems don't remove a file that is still open
+		LOCK.lock().unwrap().take();
+		fs::remove_file(session_file(&dir, &own, LOCK_EXTENSION)).ok();
+	}
+}
+
+pub fn save(backup: &Backup) -> Result<(), Box<dyn Error>> {
+	let dir = dir().ok_or("no data directory available")?;
+	let data = ron::to_string(backup)?;
+	// write then rename, so that a crash while saving doesn't corrupt the previous backup
+	let backup = session_file(&dir, &own_session(), BACKUP_EXTENSION);
+	let tmp = backup.with_extension("tmp");
+	fs::write(&tmp, data)?;
+	fs::rename(tmp, backup)?;
+	Ok(())
+}
+
+/// The backup left by the instance that crashed, see `start_session`
+pub fn load() -> Result<Backup, Box<dyn Error>> {
+	let path = recovered_path().ok_or("no data directory available")?;
+	let data = fs::read_to_string(&path)?;
+	Ok(ron::from_str(&data)?)
+}
+
+pub fn recovered_path() -> Option<PathBuf> {
+	dir().map(|dir| recovered_file(&dir, &own_session()))
+}
+
+/// Forgets the backup left by the instance that crashed
+pub fn discard() {
+	if let Some(path) = recovered_path() {
+		fs::remove_file(path).ok();
+	}
+}
+
+pub fn clear() {
+	if let Some(dir) = dir() {
+		fs::remove_file(session_file(&dir, &own_session(), BACKUP_EXTENSION)).ok();
+	}
+}
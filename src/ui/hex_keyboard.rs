@@ -0,0 +1,16 @@
+use crate::state::editors::sheet_editor::{HexLayout, State};
+use crate::util::ui::*;
+use crate::widget::sheet_editor::HexKeyboard;
+use druid::{
+	widget::{Flex, WidgetExt},
+	Widget,
+};
+
+pub fn build() -> impl Widget<State> {
+	let steps = Flex::row()
+		.with_child(make_field("degrees to the right", HexLayout::right))
+		.with_child(make_field("degrees up to the right", HexLayout::up_right))
+		.lens(State::hex_layout);
+
+	Flex::column().with_child(steps).with_flex_child(HexKeyboard::new(), 1.0)
+}
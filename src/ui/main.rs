@@ -1,14 +1,39 @@
 use druid::{
-	widget::{Button, Flex, Label},
-	Widget, WidgetExt,
+	widget::{Button, Checkbox, Controller, Flex, Label},
+	Env, Event, EventCtx, TimerToken, Widget, WidgetExt,
 };
+use std::time::Duration;
 
 use crate::commands;
+use crate::data::{layout::Metronome, sheet::LoopRegion};
 use crate::state::*;
 use crate::widget::{common::*, *};
 
 use super::sheet_editor;
 
+const STATUS_DURATION: Duration = Duration::from_secs(5);
+
+// sets the status when asked to, and clears it after a while unless another one came since
+struct ShowStatus {
+	timer: TimerToken,
+}
+
+impl<W: Widget<String>> Controller<String, W> for ShowStatus {
+	fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut String, env: &Env) {
+		match event {
+			Event::Command(cmd) if cmd.is(commands::SHOW_STATUS) => {
+				*data = cmd.get_unchecked(commands::SHOW_STATUS).clone();
+				self.timer = ctx.request_timer(STATUS_DURATION);
+			}
+			Event::Timer(id) if *id == self.timer => {
+				data.clear();
+			}
+			_ => {}
+		}
+		child.event(ctx, event, data, env);
+	}
+}
+
 pub fn build() -> impl Widget<State> {
 	let menu = {
 		Flex::row()
@@ -26,6 +51,9 @@ pub fn build() -> impl Widget<State> {
 					.with_item(dropdown::Item::new("Save As", |ctx, _, _| {
 						ctx.submit_command(commands::PROJECT_SAVE_AS)
 					}))
+					.with_item(dropdown::Item::new("Export WAV", |ctx, _, _| {
+						ctx.submit_command(commands::PROJECT_EXPORT)
+					}))
 					.fix_width(80.0)
 					.padding(3.0),
 			)
@@ -41,7 +69,58 @@ pub fn build() -> impl Widget<State> {
 					.fix_width(80.0)
 					.padding(3.0),
 			)
+			.with_child(
+				Button::new("Synth")
+					.on_click(|ctx, _, _| ctx.submit_command(commands::OPEN_SYNTH_EDITOR))
+					.fix_width(80.0)
+					.padding(3.0),
+			)
+			.with_child(
+				Button::new("Hex")
+					.on_click(|ctx, _, _| ctx.submit_command(commands::OPEN_HEX_KEYBOARD))
+					.fix_width(80.0)
+					.padding(3.0),
+			)
 			.with_flex_spacer(1.0)
+			.with_child(
+				Label::new(|status: &String, _env: &Env| status.clone())
+					.controller(ShowStatus {
+						timer: TimerToken::INVALID,
+					})
+					.lens(State::status)
+					.padding(3.0),
+			)
+			.with_child(
+				Checkbox::new("Keys")
+					.lens(editors::sheet_editor::State::keyboard)
+					.lens(editors::State::sheet_editor)
+					.lens(State::editors)
+					.padding(3.0),
+			)
+			.with_child(
+				Checkbox::new("Rec")
+					.lens(editors::sheet_editor::State::recording)
+					.lens(editors::State::sheet_editor)
+					.lens(State::editors)
+					.padding(3.0),
+			)
+			.with_child(
+				Checkbox::new("Loop")
+					.lens(LoopRegion::enabled)
+					.lens(editors::sheet_editor::State::loop_region)
+					.lens(editors::State::sheet_editor)
+					.lens(State::editors)
+					.padding(3.0),
+			)
+			.with_child(
+				Flex::row()
+					.with_child(Checkbox::new("Click").lens(Metronome::enabled).padding(3.0))
+					.with_child(Checkbox::new("Subdivisions").lens(Metronome::subdivisions).padding(3.0))
+					.lens(editors::sheet_editor::State::metronome)
+					.lens(editors::State::sheet_editor)
+					.lens(State::editors),
+			)
+			.with_child(Meter::new().lens(State::meters).padding(3.0))
 			.with_child(Label::new("BPM:"))
 			.with_child(
 				ParseLazy::new(TextBox::new())
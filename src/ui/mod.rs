@@ -3,11 +3,13 @@ use druid::Widget;
 
 use crate::state::*;
 
+pub mod hex_keyboard;
 pub mod layout_editor;
 pub mod main;
 pub mod modal;
 pub mod settings_editor;
 pub mod sheet_editor;
+pub mod synth_editor;
 
 pub fn build() -> impl Widget<State> {
 	main::build()
@@ -24,3 +26,13 @@ pub fn build_settings() -> impl Widget<State> {
 		.lens(editors::State::settings)
 		.lens(State::editors)
 }
+
+pub fn build_synth_editor() -> impl Widget<State> {
+	synth_editor::build().lens(editors::State::patch).lens(State::editors)
+}
+
+pub fn build_hex_keyboard() -> impl Widget<State> {
+	hex_keyboard::build()
+		.lens(editors::State::sheet_editor)
+		.lens(State::editors)
+}
@@ -0,0 +1,5 @@
+use super::Modal;
+
+pub fn build(message: String) -> Modal {
+	super::prompt(message, vec![("Ok", None)])
+}
@@ -1 +1,42 @@
+use druid::{
+	theme,
+	widget::{Button, Flex, Label},
+	BoxConstraints, Env, Selector, Widget, WidgetExt,
+};
+
+use crate::state::State;
+use crate::widget::*;
+
+pub mod error;
+pub mod recover;
 pub mod save;
+
+pub type Modal = (BoxConstraints, Box<dyn Fn(&Env) -> Box<dyn Widget<State>>>);
+
+/// A message with a row of buttons, each one closes the modal and then submits its command if it has one
+pub fn prompt(message: impl Into<String>, choices: Vec<(&'static str, Option<Selector>)>) -> Modal {
+	let message = message.into();
+	(
+		BoxConstraints::tight((300.0, 80.0).into()),
+		Box::new(move |env| {
+			let mut buttons = Flex::row();
+			for &(text, cmd) in &choices {
+				buttons.add_flex_child(
+					Button::new(text).on_click(move |ctx, _, _| {
+						ctx.submit_command(overlay::HIDE.to(ctx.window_id()));
+						if let Some(cmd) = cmd {
+							ctx.submit_command(cmd.to(ctx.window_id()));
+						}
+					}),
+					1.0,
+				);
+			}
+			Box::new(
+				Flex::column()
+					.with_flex_child(Label::new(message.clone()), 1.0)
+					.with_child(buttons)
+					.background(env.get(theme::BACKGROUND_LIGHT)),
+			)
+		}),
+	)
+}
@@ -0,0 +1,12 @@
+use super::Modal;
+use crate::commands;
+
+pub fn build() -> Modal {
+	super::prompt(
+		"Recover unsaved work after the crash ?",
+		vec![
+			("Restore", Some(commands::RECOVERY_RESTORE)),
+			("Discard", Some(commands::RECOVERY_DISCARD)),
+		],
+	)
+}
@@ -1,53 +1,138 @@
 use crate::commands as cmds;
-use crate::state::editors::settings::{Backend, State};
+use crate::state::editors::settings::{
+	AudioOutput, Choice, KeyMapping, MidiInput, MidiMode, MidiOutput, OscOutput, State, BUFFER_SIZES, SAMPLE_RATES,
+};
+use crate::util::ui::*;
 use crate::widget::common::*;
 use druid::{
-	lens::Map,
-	widget::{Button, Controller, Flex, Label, ViewSwitcher, WidgetExt},
-	Env, LifeCycle, LifeCycleCtx, Widget,
+	widget::{Button, Checkbox, Controller, Flex, WidgetExt},
+	Env, LifeCycle, LifeCycleCtx, Selector, Widget, WidgetId,
 };
-use std::rc::Rc;
 
-struct RequestMPEPorts;
+// asks the delegate for the choices of the selector, which depend on the system
+struct RequestChoices(Selector<WidgetId>);
 
-impl<T, W: Widget<T>> Controller<T, W> for RequestMPEPorts {
+impl<T, W: Widget<T>> Controller<T, W> for RequestChoices {
 	fn lifecycle(&mut self, child: &mut W, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
 		if let LifeCycle::WidgetAdded = event {
-			ctx.submit_command(cmds::BACKEND_MPE_REQUEST_PORTS.with(ctx.widget_id()));
+			ctx.submit_command(self.0.with(ctx.widget_id()));
 		}
 		child.lifecycle(ctx, event, data, env);
 	}
 }
 
 pub fn build() -> impl Widget<State> {
-	let backend_input = Flex::row()
+	let sample_rates = std::iter::once("Default rate".to_string())
+		.chain(SAMPLE_RATES.iter().map(|rate| format!("{} Hz", rate)))
+		.collect();
+	let buffer_sizes = std::iter::once("Default buffer".to_string())
+		.chain(BUFFER_SIZES.iter().map(|size| format!("{} frames", size)))
+		.collect();
+	let synth_output = Flex::row()
+		.with_child(Checkbox::new("Integrated synth").lens(State::synth).padding(10.0))
 		.with_child(
-			ValueSelector::new(vec![Backend::Audio, Backend::MPE { port: 0 }])
-				.fix_width(100.0)
-				.padding(10.0),
+			Flex::row()
+				.with_child(
+					IndexSelector::new(vec!["waiting for devices...".into()])
+						.fix_width(300.0)
+						.controller(RequestChoices(cmds::BACKEND_AUDIO_REQUEST_DEVICES))
+						.lens(Choice::index)
+						.lens(AudioOutput::device),
+				)
+				.with_child(IndexSelector::new(sample_rates).fix_width(150.0).padding(10.0).lens(AudioOutput::sample_rate))
+				.with_child(IndexSelector::new(buffer_sizes).fix_width(150.0).lens(AudioOutput::buffer_size))
+				.lens(State::audio),
 		)
-		.with_flex_child(
-			ViewSwitcher::new(
-				|data: &Backend, _| Rc::new(std::mem::discriminant(data)),
-				|_, data, _| {
-					Box::new(match data {
-						Backend::Audio => Flex::row().with_child(Label::new("Use integrated synth")),
-						Backend::MPE { .. } => Flex::row().with_child(
-							IndexSelector::new(vec!["waiting for ports...".into()])
-								.fix_width(300.0)
-								.controller(RequestMPEPorts)
-								.lens(enum_lens!(Backend::MPE, port)),
-						),
-					})
-				},
-			),
-			1.0,
+		.expand_width();
+
+	let mpe_output = Flex::column()
+		.with_child(
+			Flex::row()
+				.with_child(Checkbox::new("MIDI").lens(MidiOutput::enabled).fix_width(100.0).padding(10.0))
+				.with_child(
+					IndexSelector::new(vec!["waiting for ports...".into()])
+						.with_placeholder("no ports")
+						.fix_width(300.0)
+						.controller(RequestChoices(cmds::BACKEND_MPE_REQUEST_PORTS))
+						.lens(Choice::index)
+						.lens(MidiOutput::port),
+				)
+				.with_child(
+					ValueSelector::new(vec![MidiMode::Mpe, MidiMode::Rotation])
+						.fix_width(150.0)
+						.padding(10.0)
+						.lens(MidiOutput::mode),
+				)
+				.with_child(make_field("bend range", MidiOutput::bend_range))
+				.expand_width(),
+		)
+		.with_child(
+			Flex::row()
+				.with_spacer(100.0)
+				.with_child(make_field("rotate from channel", MidiOutput::first_channel))
+				.with_child(make_field("to", MidiOutput::last_channel))
+				.with_child(Checkbox::new("Steal the oldest note").lens(MidiOutput::stealing).padding(10.0))
+				.expand_width(),
+		)
+		.lens(State::mpe);
+
+	let osc_output = Flex::column()
+		.with_child(
+			Flex::row()
+				.with_child(Checkbox::new("OSC").lens(OscOutput::enabled).fix_width(100.0).padding(10.0))
+				.with_child(make_field("target", OscOutput::target))
+				.expand_width(),
 		)
-		.lens(State::backend);
+		.with_child(
+			Flex::row()
+				.with_spacer(100.0)
+				.with_child(make_field("note on", OscOutput::note_on))
+				.with_child(make_field("note off", OscOutput::note_off))
+				.with_child(make_field("frequency", OscOutput::change_freq))
+				.expand_width(),
+		)
+		.lens(State::osc);
+
+	let midi_input = Flex::row()
+		.with_child(Checkbox::new("MIDI input").lens(MidiInput::enabled).fix_width(100.0).padding(10.0))
+		.with_child(
+			IndexSelector::new(vec!["waiting for ports...".into()])
+				.with_placeholder("no ports")
+				.fix_width(300.0)
+				.controller(RequestChoices(cmds::MIDI_INPUT_REQUEST_PORTS))
+				.lens(Choice::index)
+				.lens(MidiInput::port),
+		)
+		.with_child(
+			ValueSelector::new(vec![KeyMapping::Tet, KeyMapping::Layout])
+				.fix_width(150.0)
+				.padding(10.0)
+				.lens(MidiInput::mapping),
+		)
+		.with_child(make_field("bend range", MidiInput::bend_range))
+		.with_child(Checkbox::new("Quantize").lens(MidiInput::quantize).padding(10.0))
+		.expand_width()
+		.lens(State::midi_input);
+
+	let midi_monitor = Flex::row()
+		.with_spacer(100.0)
+		.with_child(Checkbox::new("Play the keys").lens(MidiInput::monitor).padding(10.0))
+		.with_child(make_field("reference key", MidiInput::reference_key))
+		.expand_width()
+		.lens(State::midi_input);
+
+	let playback = Flex::row()
+		.with_child(Checkbox::new("Return to start on stop").lens(State::return_to_start).padding(10.0))
+		.expand_width();
 
 	Flex::column()
 		.with_flex_spacer(1.0)
-		.with_flex_child(backend_input, 1.0)
+		.with_child(synth_output)
+		.with_child(mpe_output)
+		.with_child(osc_output)
+		.with_child(midi_input)
+		.with_child(midi_monitor)
+		.with_child(playback)
 		.with_flex_spacer(1.0)
 		.with_flex_child(
 			Button::new("Apply").on_click(|ctx, _, _| ctx.submit_command(cmds::SETTINGS_APPLY.to(ctx.window_id()))),
@@ -0,0 +1,154 @@
+use crate::commands;
+use crate::data::synth::{
+	Delay, Effects, Envelope, Filter, FilterKind, Partials, Patch, Reverb, Stealing, Voices, Waveform, Wavetable,
+};
+use crate::util::ui::*;
+use crate::widget::common::*;
+use druid::{
+	lens::Map,
+	widget::{Button, Checkbox, Flex, Label, ViewSwitcher, WidgetExt},
+	Widget,
+};
+use std::rc::Rc;
+
+pub fn build() -> impl Widget<Patch> {
+	let oscillator = Flex::row()
+		.with_child(Label::new("Oscillator").fix_width(100.0).padding(10.0))
+		.with_child(
+			ValueSelector::new(vec![
+				Waveform::Sine,
+				Waveform::Saw,
+				Waveform::Square,
+				Waveform::Additive,
+				Waveform::Sample,
+				Waveform::Wavetable,
+				Waveform::Fm,
+			])
+			.fix_width(100.0)
+			.padding(10.0)
+			.lens(Patch::waveform),
+		)
+		.with_child(make_field("gain", Patch::gain))
+		.expand_width();
+
+	let partials = Flex::row()
+		.with_child(Label::new("Partials").fix_width(100.0).padding(10.0))
+		.with_child(
+			ValueSelector::new(vec![
+				Partials::Stretched { count: 8 },
+				Partials::Custom {
+					spectrum: "1:1 2:0.5 3:0.33 4:0.25".parse().unwrap(),
+				},
+			])
+			.fix_width(100.0)
+			.padding(10.0),
+		)
+		.with_flex_child(
+			ViewSwitcher::new(
+				|data: &Partials, _| Rc::new(std::mem::discriminant(data)),
+				|_, data, _| {
+					Box::new(match data {
+						Partials::Stretched { .. } => Flex::row()
+							.with_child(make_field("#harmonics", enum_lens!(Partials::Stretched, count)))
+							.with_child(Label::new("stretched to the period of the layout")),
+						Partials::Custom { .. } => {
+							Flex::row().with_child(make_field("ratio:amplitude", enum_lens!(Partials::Custom, spectrum)))
+						}
+					})
+				},
+			),
+			1.0,
+		)
+		.expand_width()
+		.lens(Patch::partials);
+
+	let samples = Flex::row()
+		.with_child(Label::new("Samples").fix_width(100.0).padding(10.0))
+		.with_flex_child(make_field("root, path[, loop start, loop end]; ...", Patch::samples), 1.0)
+		.expand_width();
+
+	let wavetable = Flex::row()
+		.with_child(Label::new("Wavetable").fix_width(100.0).padding(10.0))
+		.with_flex_child(make_field("path", Wavetable::path), 1.0)
+		.with_child(make_field("frame size", Wavetable::frame_size))
+		.with_child(make_field("position", Wavetable::position))
+		.expand_width()
+		.lens(Patch::wavetable);
+
+	let fm = Flex::row()
+		.with_child(Label::new("FM").fix_width(100.0).padding(10.0))
+		.with_flex_child(make_field("ratio:index, from the carrier", Patch::operators), 1.0)
+		.expand_width();
+
+	let envelope = Flex::row()
+		.with_child(Label::new("Envelope").fix_width(100.0).padding(10.0))
+		.with_child(make_field("attack", Envelope::attack))
+		.with_child(make_field("decay", Envelope::decay))
+		.with_child(make_field("sustain", Envelope::sustain))
+		.with_child(make_field("release", Envelope::release))
+		.expand_width()
+		.lens(Patch::envelope);
+
+	let filter = Flex::row()
+		.with_child(Label::new("Filter").fix_width(100.0).padding(10.0))
+		.with_child(
+			ValueSelector::new(vec![FilterKind::Lowpass, FilterKind::Bandpass, FilterKind::Highpass])
+				.fix_width(100.0)
+				.padding(10.0)
+				.lens(Filter::kind),
+		)
+		.with_child(make_field("cutoff (Hz)", Filter::cutoff))
+		.with_child(make_field("resonance", Filter::resonance))
+		.expand_width()
+		.lens(Patch::filter);
+
+	let voices = Flex::row()
+		.with_child(Label::new("Voices").fix_width(100.0).padding(10.0))
+		.with_child(make_field("polyphony", Voices::polyphony))
+		.with_child(
+			ValueSelector::new(vec![Stealing::Oldest, Stealing::Quietest, Stealing::ReleasedFirst])
+				.fix_width(150.0)
+				.padding(10.0)
+				.lens(Voices::stealing),
+		)
+		.expand_width()
+		.lens(Patch::voices);
+
+	let delay = Flex::row()
+		.with_child(Checkbox::new("Delay").lens(Delay::enabled).fix_width(100.0).padding(10.0))
+		.with_child(make_field("time (s)", Delay::time))
+		.with_child(Checkbox::new("sync").lens(Delay::synced).padding(10.0))
+		.with_child(make_field("beats", Delay::beats))
+		.with_child(make_field("feedback", Delay::feedback))
+		.with_child(make_field("mix", Delay::mix))
+		.expand_width()
+		.lens(Effects::delay)
+		.lens(Patch::effects);
+
+	let reverb = Flex::row()
+		.with_child(Checkbox::new("Reverb").lens(Reverb::enabled).fix_width(100.0).padding(10.0))
+		.with_child(make_field("size", Reverb::size))
+		.with_child(make_field("damping", Reverb::damping))
+		.with_child(make_field("mix", Reverb::mix))
+		.expand_width()
+		.lens(Effects::reverb)
+		.lens(Patch::effects);
+
+	Flex::column()
+		.with_flex_spacer(1.0)
+		.with_child(oscillator)
+		.with_child(partials)
+		.with_child(samples)
+		.with_child(wavetable)
+		.with_child(fm)
+		.with_child(envelope)
+		.with_child(filter)
+		.with_child(voices)
+		.with_child(delay)
+		.with_child(reverb)
+		.with_flex_spacer(1.0)
+		.with_flex_child(
+			Button::new("Apply").on_click(|ctx, _, _| ctx.submit_command(commands::SYNTH_APPLY.to(ctx.window_id()))),
+			1.0,
+		)
+}
@@ -24,6 +24,7 @@ pub struct IndexSelector {
 	label: Label<()>,
 	label_size: Size,
 	choices: Vec<String>,
+	placeholder: String, // shown when there is nothing to choose from
 	hover: Side,
 }
 
@@ -34,9 +35,25 @@ impl IndexSelector {
 			label: Label::new(choices[0].clone()),
 			label_size: Size::ZERO,
 			choices,
+			placeholder: "none".to_string(),
 			hover: None,
 		}
 	}
+
+	/// Builder-style method to set the text shown when there are no choices.
+	pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+		self.placeholder = placeholder.into();
+		self
+	}
+
+	// the index can be ahead of the choices, until the new ones are set
+	fn update_label(&mut self, idx: usize) {
+		let text = match self.choices.last() {
+			Some(last) => self.choices.get(idx).unwrap_or(last),
+			None => &self.placeholder,
+		};
+		self.label.set_text(text.clone());
+	}
 }
 
 impl Widget<usize> for IndexSelector {
@@ -52,14 +69,15 @@ impl Widget<usize> for IndexSelector {
 					ctx.set_active(false);
 					ctx.request_paint();
 					ctx.request_layout();
-					if ctx.is_hot() {
+					// without choices, the index is kept for when they come back
+					if ctx.is_hot() && !self.choices.is_empty() {
 						match self.hover {
 							Left => *data = data.saturating_sub(1),
 							Right => *data = data.saturating_add(1).min(self.choices.len() - 1),
 							None => (),
 						}
 					}
-					self.label.set_text(self.choices[*data].clone());
+					self.update_label(*data);
 				}
 			}
 			Event::MouseMove(MouseEvent { pos, .. }) => {
@@ -74,7 +92,7 @@ impl Widget<usize> for IndexSelector {
 			Event::Command(cmd) if cmd.is(SET_CHOICES) => {
 				let choices = cmd.get_unchecked(SET_CHOICES).clone();
 				self.choices = choices;
-				self.label.set_text(self.choices[*data].clone());
+				self.update_label(*data);
 				ctx.request_layout();
 				ctx.request_paint();
 			}
@@ -82,9 +100,10 @@ impl Widget<usize> for IndexSelector {
 		}
 	}
 
-	fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &usize, env: &Env) {
+	fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &usize, env: &Env) {
 		match event {
 			LifeCycle::WidgetAdded => {
+				self.update_label(*data);
 				self.label.lifecycle(ctx, event, &(), env);
 			}
 			LifeCycle::HotChanged(hot) => {
@@ -99,7 +118,7 @@ impl Widget<usize> for IndexSelector {
 
 	fn update(&mut self, _ctx: &mut UpdateCtx, old_data: &usize, data: &usize, _env: &Env) {
 		if old_data != data {
-			self.label.set_text(self.choices[*data].clone());
+			self.update_label(*data);
 		}
 	}
 
@@ -0,0 +1,97 @@
+use druid::{
+	BoxConstraints, Color, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Rect, RenderContext, Size,
+	UpdateCtx, Widget,
+};
+use std::sync::Arc;
+
+use crate::server::{meters::MAX_CHANNELS, Meters};
+use crate::theme;
+
+const MIN_DB: f64 = -60.0;
+const PEAK_FALL: f64 = 20.0; // in dB per second
+const CLIP_HOLD: f64 = 2.0; // in seconds
+
+fn to_db(x: f64) -> f64 {
+	20.0 * x.max(1e-6).log10()
+}
+
+/// Level meters of the synth output, one bar per channel
+pub struct Meter {
+	peaks: [f64; MAX_CHANNELS], // in dB
+	rms: [f64; MAX_CHANNELS],
+	clip: f64, // time left showing the clip indicator
+}
+
+impl Meter {
+	pub fn new() -> Meter {
+		Meter {
+			peaks: [MIN_DB; MAX_CHANNELS],
+			rms: [MIN_DB; MAX_CHANNELS],
+			clip: 0.0,
+		}
+	}
+}
+
+impl Widget<Arc<Meters>> for Meter {
+	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut Arc<Meters>, _env: &Env) {
+		if let Event::AnimFrame(delta) = event {
+			let delta = (*delta as f64) / 1000000000.0;
+			for ch in 0..data.nb_channels() {
+				let (peak, rms) = data.read(ch);
+				if peak > 1.0 {
+					self.clip = CLIP_HOLD;
+				}
+				self.peaks[ch] = to_db(peak).max(self.peaks[ch] - PEAK_FALL * delta);
+				self.rms[ch] = to_db(rms);
+			}
+			self.clip = (self.clip - delta).max(0.0);
+			ctx.request_paint();
+			ctx.request_anim_frame();
+		}
+	}
+
+	fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &Arc<Meters>, _env: &Env) {
+		if let LifeCycle::WidgetAdded = event {
+			ctx.request_anim_frame();
+		}
+	}
+
+	fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &Arc<Meters>, _data: &Arc<Meters>, _env: &Env) {}
+
+	fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &Arc<Meters>, _env: &Env) -> Size {
+		bc.constrain(Size::new(120.0, 24.0))
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, data: &Arc<Meters>, env: &Env) {
+		let size = ctx.size();
+		let rect = Rect::from_origin_size((0.0, 0.0), size);
+		ctx.fill(rect, &env.get(theme::BACKGROUND_0));
+
+		let nb_channels = data.nb_channels();
+		if nb_channels == 0 {
+			return;
+		}
+		let clip_width = 6.0;
+		let width = size.width - clip_width - 2.0;
+		let height = size.height / nb_channels as f64;
+		let x = |db: f64| ((db - MIN_DB) / -MIN_DB).max(0.0).min(1.0) * width;
+		for ch in 0..nb_channels {
+			let y0 = ch as f64 * height;
+			ctx.fill(
+				Rect::new(0.0, y0 + 1.0, x(self.rms[ch]), y0 + height - 1.0),
+				&env.get(theme::FEATURE_COLOR),
+			);
+			let peak_x = x(self.peaks[ch]);
+			ctx.fill(
+				Rect::new((peak_x - 2.0).max(0.0), y0 + 1.0, peak_x, y0 + height - 1.0),
+				&env.get(theme::HIGHLIGHTED_COLOR),
+			);
+		}
+		let clip_color = if self.clip > 0.0 {
+			Color::rgb8(0xE0, 0x30, 0x30)
+		} else {
+			env.get(theme::BACKGROUND_2)
+		};
+		ctx.fill(Rect::new(size.width - clip_width, 0.0, size.width, size.height), &clip_color);
+	}
+}
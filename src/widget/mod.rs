@@ -7,3 +7,6 @@ pub use overlay::Overlay;
 
 pub mod dropdown;
 pub use dropdown::DropDown;
+
+pub mod meter;
+pub use meter::Meter;
@@ -22,6 +22,24 @@ mod notes;
 pub const ADD_RELATIVE_NOTE: Selector<(Index, f64)> = Selector::new("sheet-editor.add-relative-note");
 pub const DUPLICATE_NOTE: Selector<(Index, f64)> = Selector::new("sheet-editor.duplicate-note");
 pub const DELETE_NOTE: Selector<Index> = Selector::new("sheet-editor.delete-note");
+pub const SET_NOTE_ATTRIBUTE: Selector<(Index, NoteAttribute, f64)> = Selector::new("sheet-editor.set-note-attribute");
+
+/// The values of a note that are set from the context menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteAttribute {
+	Pan,
+	Pressure,
+	Timbre,
+}
+impl NoteAttribute {
+	fn of(self, note: &mut Note) -> &mut f64 {
+		match self {
+			NoteAttribute::Pan => &mut note.pan,
+			NoteAttribute::Pressure => &mut note.pressure,
+			NoteAttribute::Timbre => &mut note.timbre,
+		}
+	}
+}
 
 #[derive(Debug, PartialEq)]
 pub enum Hover {
@@ -76,7 +94,7 @@ impl Board {
 		self.action = Action::Idle;
 		ctx.set_active(false);
 		ctx.request_paint();
-		let cmd = commands::ICP.with(icp::Event::NoteStop(2000));
+		let cmd = commands::ICP.with(icp::Event::NoteStop(icp::BOARD_ID));
 		ctx.submit_command(cmd.to(ctx.window_id()));
 	}
 }
@@ -132,8 +150,11 @@ impl Widget<State> for Board {
 									ctx.submit_command(
 										commands::ICP
 											.with(icp::Event::NotePlay(icp::Note {
-												id: 2000,
+												id: icp::BOARD_ID,
 												freq: sheet.get_freq(note.pitch),
+												pan: note.pan,
+												pressure: note.pressure,
+												timbre: note.timbre,
 											}))
 											.to(ctx.window_id()),
 									);
@@ -169,8 +190,11 @@ impl Widget<State> for Board {
 									ctx.submit_command(
 										commands::ICP
 											.with(icp::Event::NotePlay(icp::Note {
-												id: 2000,
+												id: icp::BOARD_ID,
 												freq: note_freq,
+												pan: note.pan,
+												pressure: note.pressure,
+												timbre: note.timbre,
 											}))
 											.to(ctx.window_id()),
 									);
@@ -235,12 +259,16 @@ impl Widget<State> for Board {
 									self.action_effective = true;
 									if sheet.get_y(note.pitch) != pos.y {
 										let note = sheet.get_note(*idx).unwrap();
-										ctx.submit_command(commands::ICP.with(icp::Event::NoteStop(2000)).to(ctx.window_id()));
+										let stop = icp::Event::NoteStop(icp::BOARD_ID);
+										ctx.submit_command(commands::ICP.with(stop).to(ctx.window_id()));
 										ctx.submit_command(
 											commands::ICP
 												.with(icp::Event::NotePlay(icp::Note {
-													id: 2000,
+													id: icp::BOARD_ID,
 													freq: sheet.get_freq(note.pitch),
+													pan: note.pan,
+													pressure: note.pressure,
+													timbre: note.timbre,
 												}))
 												.to(ctx.window_id()),
 										);
@@ -317,6 +345,9 @@ impl Widget<State> for Board {
 					start: time,
 					length: self.note_len,
 					pitch: Pitch::Relative(root, Interval::Ratio(3, 2)),
+					pan: sheet.get_note(root).map(|root| root.pan).unwrap_or(0.0),
+					pressure: sheet.get_note(root).map(|root| root.pressure).unwrap_or(0.0),
+					timbre: sheet.get_note(root).map(|root| root.timbre).unwrap_or(icp::DEFAULT_TIMBRE),
 				});
 				sheet.add_note(note);
 				sheet_changed = true;
@@ -328,6 +359,9 @@ impl Widget<State> for Board {
 						start: time,
 						length: original.length,
 						pitch: original.pitch,
+						pan: original.pan,
+						pressure: original.pressure,
+						timbre: original.timbre,
 					});
 					sheet.add_note(note);
 					sheet_changed = true;
@@ -338,6 +372,16 @@ impl Widget<State> for Board {
 				sheet.remove_note(id);
 				sheet_changed = true;
 			}
+			Event::Command(ref cmd) if cmd.is(SET_NOTE_ATTRIBUTE) => {
+				let (id, attribute, value) = *cmd.get_unchecked(SET_NOTE_ATTRIBUTE);
+				for id in menu_targets(data, id) {
+					if let Some(note) = sheet.get_note_mut(id) {
+						*attribute.of(note) = value;
+					}
+				}
+				sheet_changed = true;
+				history_save = true;
+			}
 			_ => {}
 		}
 		if sheet_changed {
@@ -419,6 +463,7 @@ impl Widget<State> for Board {
 }
 
 fn make_note_context_menu<T: Data>(id: Index, time: f64) -> MenuDesc<T> {
+	let pan = |value| SET_NOTE_ATTRIBUTE.with((id, NoteAttribute::Pan, value));
 	MenuDesc::empty()
 		.append(MenuItem::new(
 			LocalizedString::new("Add relative note"),
@@ -429,6 +474,36 @@ fn make_note_context_menu<T: Data>(id: Index, time: f64) -> MenuDesc<T> {
 			DUPLICATE_NOTE.with((id, time)),
 		))
 		.append(MenuItem::new(LocalizedString::new("Delete note"), DELETE_NOTE.with(id)))
+		.append(
+			MenuDesc::new(LocalizedString::new("Pan"))
+				.append(MenuItem::new(LocalizedString::new("Left"), pan(-1.0)))
+				.append(MenuItem::new(LocalizedString::new("Half left"), pan(-0.5)))
+				.append(MenuItem::new(LocalizedString::new("Center"), pan(0.0)))
+				.append(MenuItem::new(LocalizedString::new("Half right"), pan(0.5)))
+				.append(MenuItem::new(LocalizedString::new("Right"), pan(1.0))),
+		)
+		.append(level_menu("Pressure", NoteAttribute::Pressure, id))
+		.append(level_menu("Timbre", NoteAttribute::Timbre, id))
+}
+
+fn level_menu<T: Data>(name: &'static str, attribute: NoteAttribute, id: Index) -> MenuDesc<T> {
+	let level = |value| SET_NOTE_ATTRIBUTE.with((id, attribute, value));
+	MenuDesc::new(LocalizedString::new(name))
+		.append(MenuItem::new(LocalizedString::new("None"), level(0.0)))
+		.append(MenuItem::new(LocalizedString::new("Quarter"), level(0.25)))
+		.append(MenuItem::new(LocalizedString::new("Half"), level(0.5)))
+		.append(MenuItem::new(LocalizedString::new("Three quarters"), level(0.75)))
+		.append(MenuItem::new(LocalizedString::new("Full"), level(1.0)))
+}
+
+// the whole selection is changed from the context menu if the note is part of it
+fn menu_targets(data: &State, id: Index) -> Vec<Index> {
+	let selection = data.selection.borrow();
+	if selection.contains(&id) {
+		selection.iter().cloned().collect()
+	} else {
+		vec![id]
+	}
 }
 
 fn get_hover(pos: Point, coord: Coord, sheet: &Sheet, env: &Env) -> Hover {
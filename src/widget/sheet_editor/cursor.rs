@@ -10,56 +10,104 @@ use crate::theme;
 use crate::commands;
 use crate::state::editors::sheet_editor::State;
 
+const LOCATOR_GRAB_DIST: f64 = 4.0; // in pixels
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Drag {
+	Cursor,
+	LoopStart,
+	LoopEnd,
+}
+
 pub struct Cursor {
-	origin: Option<f64>,
+	drag: Option<Drag>,
+	last_playhead: Option<f64>,
 }
 
 impl Cursor {
 	pub fn new() -> Cursor {
-		Cursor { origin: None }
+		Cursor {
+			drag: None,
+			last_playhead: None,
+		}
 	}
 }
 
 impl Widget<State> for Cursor {
 	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut State, _env: &Env) {
-		let position = &mut data.cursor;
 		let view = data.frame.x.view;
 		let size = ctx.size();
+		let to_time = |x: f64| ((x / size.width) * view.size() + view.0).max(0.0);
+		let to_screen = |time: f64| ((time - view.0) / view.size()) * size.width;
 		match event {
 			Event::MouseDown(MouseEvent {
 				button: MouseButton::Left,
 				pos,
+				mods,
 				..
 			}) => {
 				ctx.request_paint();
 				ctx.set_active(true);
-				let new_position = (pos.x / size.width) * view.size() + view.0;
-				*position = new_position;
+				let region = &mut data.loop_region;
+				// a disabled loop has no locators, clicking near them moves the cursor
+				let enabled = region.enabled;
+				let near = |time: f64| enabled && (to_screen(time) - pos.x).abs() < LOCATOR_GRAB_DIST;
+				self.drag = Some(if mods.shift() {
+					// dragging with shift draws a new loop region
+					region.start = to_time(pos.x);
+					region.end = region.start;
+					region.enabled = true;
+					Drag::LoopEnd
+				} else if near(region.start) {
+					Drag::LoopStart
+				} else if near(region.end) {
+					Drag::LoopEnd
+				} else {
+					data.cursor = to_time(pos.x);
+					Drag::Cursor
+				});
 			}
 			Event::MouseMove(MouseEvent { pos, .. }) => {
 				if ctx.is_active() {
 					ctx.request_paint();
-					let new_position = (pos.x / size.width) * view.size() + view.0;
-					*position = new_position.max(0.0);
+					let time = to_time(pos.x);
+					match self.drag {
+						Some(Drag::Cursor) => data.cursor = time,
+						Some(Drag::LoopStart) => data.loop_region.start = time,
+						Some(Drag::LoopEnd) => data.loop_region.end = time,
+						None => {}
+					}
+				}
+			}
+			Event::MouseUp(_) => {
+				if ctx.is_active() {
+					ctx.set_active(false);
+					let region = &mut data.loop_region;
+					if region.end < region.start {
+						std::mem::swap(&mut region.start, &mut region.end);
+					}
+					self.drag = None;
 				}
 			}
-			Event::MouseUp(MouseEvent {
-				button: MouseButton::Left,
-				..
-			}) => ctx.set_active(false),
 			Event::Command(ref cmd) if cmd.is(commands::PLAY_START) => {
-				self.origin = Some(*position);
+				// the server may still be showing where it stopped last time
+				self.last_playhead = data.playhead.read();
 				ctx.request_anim_frame();
 			}
 			Event::Command(ref cmd) if cmd.is(commands::PLAY_STOP) => {
-				*position = self.origin.unwrap();
-				self.origin = None;
 				ctx.request_paint();
 			}
 			Event::AnimFrame(delta) => {
-				if self.origin.is_some() {
-					let delta = (*delta as f64) / 1000000000.0;
-					*position = (*position + delta * (data.tempo / 60.0)) % data.sheet.borrow().get_size();
+				if data.playing {
+					let playhead = data.playhead.read();
+					if playhead.is_some() && playhead != self.last_playhead {
+						data.cursor = playhead.unwrap();
+					} else {
+						// the server updates less often than the screen, the cursor moves on its own in between
+						let delta = (*delta as f64) / 1000000000.0;
+						data.cursor = advance(data, delta * (data.tempo / 60.0));
+					}
+					self.last_playhead = playhead;
 					ctx.request_paint();
 					ctx.request_anim_frame();
 				}
@@ -74,6 +122,13 @@ impl Widget<State> for Cursor {
 		if old_data.tempo != data.tempo {
 			ctx.submit_command(commands::TEMPO_CHANGED.with(data.tempo).to(ctx.window_id()));
 		}
+		if old_data.loop_region != data.loop_region {
+			ctx.submit_command(commands::LOOP_CHANGED.with(data.loop_region).to(ctx.window_id()));
+			ctx.request_paint();
+		}
+		if old_data.metronome != data.metronome {
+			ctx.submit_command(commands::METRONOME_CHANGED.with(data.metronome).to(ctx.window_id()));
+		}
 	}
 
 	fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &State, _env: &Env) -> Size {
@@ -89,12 +144,35 @@ impl Widget<State> for Cursor {
 
 		paint_ctx.fill(rect, &env.get(theme::NEUTRAL_COLOR_1));
 
-		let (pos, view) = (data.cursor, data.frame.x.view);
-		let screen_pos = ((pos - view.0) / view.size()) * size.width;
+		let view = data.frame.x.view;
+		let to_screen = |time: f64| ((time - view.0) / view.size()) * size.width;
 
+		let region = &data.loop_region;
+		if region.enabled {
+			let (start, end) = (to_screen(region.start), to_screen(region.end));
+			let color = env.get(theme::FEATURE_COLOR);
+			paint_ctx.fill(
+				Rect::from_points((start.min(end), 0.0), (start.max(end), size.height)),
+				&color.clone().with_alpha(0.3),
+			);
+			for &x in &[start, end] {
+				paint_ctx.stroke(Line::new((x, 0.0), (x, size.height)), &color, 2.0);
+			}
+		}
+
+		let screen_pos = to_screen(data.cursor);
 		let p0 = Point::new(screen_pos, 0.0);
 		let p1 = Point::new(screen_pos, size.height);
 		let line = Line::new(p0, p1);
 		paint_ctx.stroke(line, &Color::rgb8(0xF0, 0xF0, 0xF0), 1.0);
 	}
 }
+
+// the cursor moved forward by `beats`, following the loop like the server does
+fn advance(data: &State, beats: f64) -> f64 {
+	let position = data.cursor + beats;
+	match data.loop_region.range() {
+		Some(region) if data.cursor < region.1 && position >= region.1 => region.0 + (position - region.1) % region.size(),
+		_ => position % data.sheet.borrow().get_size(),
+	}
+}
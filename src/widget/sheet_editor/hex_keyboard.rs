@@ -0,0 +1,199 @@
+use druid::kurbo::BezPath;
+
+use druid::{
+	BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point, Rect,
+	RenderContext, Size, UpdateCtx, Vec2, Widget,
+};
+
+use crate::commands;
+use crate::data::icp;
+use crate::state::editors::sheet_editor::State;
+use crate::theme;
+
+use super::keyboard::{add_note, degree_freq, entry, position, record_note, step_end, Entry};
+
+const KEY_RADIUS: f64 = 20.0; // in pixels, from the center to a corner
+
+type Key = (i64, i64); // axial coordinates, along the rows and up to the right
+
+struct Pressed {
+	key: Key,
+	start: f64,
+	freq: f64,
+	entry: Entry,
+}
+
+/// An isomorphic keyboard of the scale of the layout at the cursor, the base of the scale being in the middle
+pub struct HexKeyboard {
+	pressed: Option<Pressed>,
+}
+
+impl HexKeyboard {
+	pub fn new() -> HexKeyboard {
+		HexKeyboard { pressed: None }
+	}
+
+	fn play(&mut self, ctx: &mut EventCtx, data: &State, key: Key) {
+		let start = position(data);
+		let freq = degree_freq(data, start, degree(data, key), 0);
+		let event = match &self.pressed {
+			Some(pressed) => {
+				if pressed.entry == Entry::Recording {
+					record_note(ctx, data, pressed.start, position(data), pressed.freq);
+				}
+				icp::Event::NoteChangeFreq(icp::HEX_KEYBOARD_ID, freq)
+			}
+			None => {
+				if data.recording && !data.playing {
+					// without playback, the note is entered at the cursor
+					add_note(ctx, data, start, step_end(data, start), freq);
+				}
+				icp::Event::NotePlay(icp::Note::new(icp::HEX_KEYBOARD_ID, freq))
+			}
+		};
+		ctx.submit_command(commands::ICP.with(event).to(ctx.window_id()));
+		let entry = entry(data);
+		self.pressed = Some(Pressed { key, start, freq, entry });
+		ctx.request_paint();
+	}
+
+	fn release(&mut self, ctx: &mut EventCtx, data: &mut State) {
+		if let Some(pressed) = self.pressed.take() {
+			ctx.submit_command(commands::ICP.with(icp::Event::NoteStop(icp::HEX_KEYBOARD_ID)).to(ctx.window_id()));
+			match pressed.entry {
+				Entry::Recording => record_note(ctx, data, pressed.start, position(data), pressed.freq),
+				Entry::Stepped if !data.playing => data.cursor = step_end(data, data.cursor),
+				_ => {}
+			}
+			ctx.request_paint();
+		}
+	}
+}
+
+fn degree(data: &State, (q, r): Key) -> i64 {
+	q * data.hex_layout.right + r * data.hex_layout.up_right
+}
+
+fn key_center(size: Size, (q, r): Key) -> Point {
+	let (q, r) = (q as f64, r as f64);
+	Point::new(
+		size.width / 2.0 + KEY_RADIUS * 3f64.sqrt() * (q + r / 2.0),
+		size.height / 2.0 - KEY_RADIUS * 1.5 * r,
+	)
+}
+
+fn key_at(size: Size, pos: Point) -> Key {
+	let r = (size.height / 2.0 - pos.y) / (KEY_RADIUS * 1.5);
+	let q = (pos.x - size.width / 2.0) / (KEY_RADIUS * 3f64.sqrt()) - r / 2.0;
+	// rounding in cube coordinates, where the component that moved the most is deduced from the others
+	let s = -q - r;
+	let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+	let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+	if dq > dr && dq > ds {
+		rq = -rr - rs;
+	} else if dr > ds {
+		rr = -rq - rs;
+	}
+	(rq as i64, rr as i64)
+}
+
+impl Widget<State> for HexKeyboard {
+	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut State, _env: &Env) {
+		let size = ctx.size();
+		match event {
+			Event::MouseDown(MouseEvent {
+				pos,
+				button: MouseButton::Left,
+				..
+			}) => {
+				ctx.set_active(true);
+				self.play(ctx, data, key_at(size, *pos));
+			}
+			Event::MouseMove(mouse) if ctx.is_active() => {
+				let key = key_at(size, mouse.pos);
+				if self.pressed.as_ref().map_or(false, |pressed| pressed.key != key) {
+					self.play(ctx, data, key);
+				}
+			}
+			Event::MouseUp(MouseEvent {
+				button: MouseButton::Left,
+				..
+			}) => {
+				ctx.set_active(false);
+				self.release(ctx, data);
+			}
+			Event::Command(cmd) if cmd.is(commands::LAYOUT_CHANGED) => {
+				ctx.request_paint();
+			}
+			Event::Command(cmd) if cmd.is(commands::RECORDING_STOPPED) => {
+				if let Some(pressed) = self.pressed.as_mut().filter(|pressed| pressed.entry == Entry::Recording) {
+					record_note(ctx, data, pressed.start, *cmd.get_unchecked(commands::RECORDING_STOPPED), pressed.freq);
+					pressed.entry = Entry::Played;
+				}
+			}
+			_ => {}
+		}
+	}
+
+	fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &State, _env: &Env) {}
+
+	fn update(&mut self, ctx: &mut UpdateCtx, old_data: &State, data: &State, _env: &Env) {
+		// the keys only change with the marker at the cursor
+		let layout = data.layout.borrow();
+		if old_data.hex_layout != data.hex_layout
+			|| layout.get_marker_at(old_data.cursor, None).0 != layout.get_marker_at(data.cursor, None).0
+		{
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &State, _env: &Env) -> Size {
+		bc.max()
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, data: &State, env: &Env) {
+		let size = ctx.size();
+		let rect = Rect::from_origin_size(Point::ORIGIN, size);
+
+		ctx.clip(rect);
+
+		ctx.fill(rect, &env.get(theme::BACKGROUND_0));
+
+		let layout = data.layout.borrow();
+		let nb_degrees = match &layout.get_marker_at(data.cursor, None).1.freq {
+			Some(pattern) => pattern.nb_degrees() as i64,
+			None => 12,
+		};
+
+		let rows = (size.height / (KEY_RADIUS * 3.0)).ceil() as i64 + 1;
+		let columns = (size.width / (KEY_RADIUS * 3f64.sqrt() * 2.0)).ceil() as i64 + 1;
+		for r in -rows..=rows {
+			// the rows lean to the right going up, the columns follow to stay on screen
+			let offset = r / 2;
+			for q in (-columns - offset - 1)..=(columns - offset + 1) {
+				let key = (q, r);
+				let center = key_center(size, key);
+				let mut hexagon = BezPath::new();
+				for i in 0..6 {
+					let angle = std::f64::consts::PI / 3.0 * (i as f64 + 0.5);
+					let corner = center + (KEY_RADIUS - 1.5) * Vec2::new(angle.cos(), angle.sin());
+					if i == 0 {
+						hexagon.move_to(corner);
+					} else {
+						hexagon.line_to(corner);
+					}
+				}
+				hexagon.close_path();
+
+				let color = if self.pressed.as_ref().map_or(false, |pressed| pressed.key == key) {
+					env.get(theme::HIGHLIGHTED_COLOR)
+				} else if degree(data, key).rem_euclid(nb_degrees) == 0 {
+					env.get(theme::FEATURE_COLOR)
+				} else {
+					env.get(theme::NEUTRAL_COLOR_2)
+				};
+				ctx.fill(hexagon, &color);
+			}
+		}
+	}
+}
@@ -0,0 +1,200 @@
+use crate::commands;
+use crate::data::{icp, sheet::Note};
+use crate::state::editors::sheet_editor::State;
+use druid::{Code, EventCtx, KeyEvent, LifeCycleCtx, Point, Target};
+use std::collections::HashMap;
+
+// each row continues the degrees of the row below it
+const ROWS: [&[Code]; 3] = [
+	&[
+		Code::KeyZ,
+		Code::KeyX,
+		Code::KeyC,
+		Code::KeyV,
+		Code::KeyB,
+		Code::KeyN,
+		Code::KeyM,
+		Code::Comma,
+		Code::Period,
+		Code::Slash,
+	],
+	&[
+		Code::KeyA,
+		Code::KeyS,
+		Code::KeyD,
+		Code::KeyF,
+		Code::KeyG,
+		Code::KeyH,
+		Code::KeyJ,
+		Code::KeyK,
+		Code::KeyL,
+		Code::Semicolon,
+		Code::Quote,
+	],
+	&[
+		Code::KeyQ,
+		Code::KeyW,
+		Code::KeyE,
+		Code::KeyR,
+		Code::KeyT,
+		Code::KeyY,
+		Code::KeyU,
+		Code::KeyI,
+		Code::KeyO,
+		Code::KeyP,
+		Code::BracketLeft,
+		Code::BracketRight,
+	],
+];
+
+fn key_degree(code: Code) -> Option<i64> {
+	ROWS.iter().flat_map(|row| row.iter()).position(|&x| x == code).map(|i| i as i64)
+}
+
+/// What becomes of the note of a key once it is released
+#[derive(Clone, Copy, PartialEq)]
+pub enum Entry {
+	Played,    // only heard
+	Stepped,   // entered at the cursor without playback, the cursor moves past it
+	Recording, // added to the sheet, until where the playback reached
+}
+
+/// How a key pressed now is entered
+pub fn entry(data: &State) -> Entry {
+	match (data.recording, data.playing) {
+		(true, true) => Entry::Recording,
+		(true, false) => Entry::Stepped,
+		_ => Entry::Played,
+	}
+}
+
+struct Held {
+	id: icp::NoteId,
+	start: f64,
+	freq: f64,
+	entry: Entry,
+}
+
+/// Plays the degrees of the scale of the layout with the letter keys,
+/// from the base of the scale at Z and shifted by one period with - and =
+pub struct Keyboard {
+	shift: i64, // in periods
+	held: HashMap<Code, Held>,
+}
+
+impl Keyboard {
+	pub fn new() -> Keyboard {
+		Keyboard {
+			shift: 0,
+			held: HashMap::new(),
+		}
+	}
+
+	/// Returns whether the key plays the keyboard
+	pub fn key_down(&mut self, ctx: &mut EventCtx, event: &KeyEvent, data: &State) -> bool {
+		if !data.keyboard || event.mods.ctrl() || event.mods.alt() || event.mods.meta() {
+			return false;
+		}
+		match event.code {
+			Code::Minus => self.shift -= 1,
+			Code::Equal => self.shift += 1,
+			code => {
+				let degree = match key_degree(code) {
+					Some(degree) => degree,
+					None => return false,
+				};
+				if event.repeat || self.held.contains_key(&code) {
+					return true;
+				}
+				let start = position(data);
+				let freq = degree_freq(data, start, degree, self.shift);
+				let id = icp::KEYBOARD_ID + degree as usize;
+				let note = icp::Note::new(id, freq);
+				ctx.submit_command(commands::ICP.with(icp::Event::NotePlay(note)).to(ctx.window_id()));
+				if data.recording && !data.playing {
+					// without playback, the notes are entered one step after another from the cursor
+					add_note(ctx, data, start, step_end(data, start), freq);
+				}
+				let entry = entry(data);
+				self.held.insert(code, Held { id, start, freq, entry });
+			}
+		}
+		true
+	}
+
+	/// Returns whether the key plays the keyboard
+	pub fn key_up(&mut self, ctx: &mut EventCtx, event: &KeyEvent, data: &mut State) -> bool {
+		let held = match self.held.remove(&event.code) {
+			Some(held) => held,
+			None => return false,
+		};
+		ctx.submit_command(commands::ICP.with(icp::Event::NoteStop(held.id)).to(ctx.window_id()));
+		match held.entry {
+			Entry::Recording => record_note(ctx, data, held.start, position(data), held.freq),
+			// the cursor moves past the step once all of its keys are released
+			Entry::Stepped if !data.playing && self.held.is_empty() => data.cursor = step_end(data, data.cursor),
+			_ => {}
+		}
+		true
+	}
+
+	/// Ends the recorded notes of the held keys where the playback stopped, the keys keep sounding
+	pub fn stop_recording(&mut self, ctx: &mut EventCtx, data: &State, stopped_at: f64) {
+		for held in self.held.values_mut().filter(|held| held.entry == Entry::Recording) {
+			record_note(ctx, data, held.start, stopped_at, held.freq);
+			held.entry = Entry::Played;
+		}
+	}
+
+	/// Stops the held notes, when the keys won't be released in the editor
+	pub fn release_all(&mut self, ctx: &mut LifeCycleCtx) {
+		for (_, held) in self.held.drain() {
+			ctx.submit_command(commands::ICP.with(icp::Event::NoteStop(held.id)).to(ctx.window_id()));
+		}
+	}
+}
+
+/// The frequency of a degree of the scale at `at`, shifted by `periods`
+pub fn degree_freq(data: &State, at: f64, degree: i64, periods: i64) -> f64 {
+	let layout = data.layout.borrow();
+	match &layout.get_marker_at(at, None).1.freq {
+		Some(pattern) => pattern.degree_freq(degree + periods * pattern.nb_degrees() as i64),
+		// 12-TET from middle C without a scale
+		None => 440.0 * 2f64.powf((degree + periods * 12 - 9) as f64 / 12.0),
+	}
+}
+
+/// Where the notes are played in the layout, and recorded
+pub fn position(data: &State) -> f64 {
+	if data.playing {
+		data.playhead.read().unwrap_or(data.cursor)
+	} else {
+		data.cursor
+	}
+}
+
+/// Where a note entered at `start` without playback ends, at the next step of the time pattern
+pub fn step_end(data: &State, start: f64) -> f64 {
+	let next = data.layout.borrow().quantize_time_after(start, start);
+	if next > start {
+		next
+	} else {
+		start + 1.0
+	}
+}
+
+pub fn add_note(ctx: &mut EventCtx, data: &State, start: f64, end: f64, freq: f64) {
+	let note = Note::new(Point::new(start, freq.log2()), end - start);
+	data.sheet.borrow_mut().add_note(note);
+	// the hex keyboard is in its own window, the board needs to know as well
+	ctx.submit_command(commands::SHEET_CHANGED.to(Target::Global));
+	ctx.submit_command(commands::HISTORY_SAVE.to(ctx.window_id()));
+}
+
+/// Adds the note played from `start` until the playback reached `position`
+pub fn record_note(ctx: &mut EventCtx, data: &State, start: f64, position: f64, freq: f64) {
+	let end = data.recorded_end(start, position);
+	if end > start {
+		add_note(ctx, data, start, end, freq);
+	}
+}
@@ -8,6 +8,11 @@ pub use board::Board;
 pub mod shortcuts;
 pub use shortcuts::Shortcuts;
 
+mod keyboard;
+
+mod hex_keyboard;
+pub use hex_keyboard::HexKeyboard;
+
 mod preview;
 pub use preview::*;
 
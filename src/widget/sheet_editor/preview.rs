@@ -35,7 +35,7 @@ impl Widget<State> for Preview {
 				self.playing = Some(freq);
 				ctx.set_active(true);
 				ctx.request_paint();
-				let cmd = commands::ICP.with(icp::Event::NotePlay(icp::Note { id: 1000, freq }));
+				let cmd = commands::ICP.with(icp::Event::NotePlay(icp::Note::new(icp::PREVIEW_ID, freq)));
 				ctx.submit_command(cmd.to(ctx.window_id()));
 			}
 			Event::MouseMove(mouse) if mouse.buttons.has_left() => {
@@ -43,7 +43,7 @@ impl Widget<State> for Preview {
 					let freq = 2f64.powf((mouse.pos.y / size.height) * range.size() + range.0);
 					*prev_freq = freq;
 					ctx.request_paint();
-					let cmd = commands::ICP.with(icp::Event::NoteChangeFreq(1000, freq));
+					let cmd = commands::ICP.with(icp::Event::NoteChangeFreq(icp::PREVIEW_ID, freq));
 					ctx.submit_command(cmd.to(ctx.window_id()));
 				}
 			}
@@ -54,7 +54,7 @@ impl Widget<State> for Preview {
 				self.playing = None;
 				ctx.set_active(false);
 				ctx.request_paint();
-				let cmd = commands::ICP.with(icp::Event::NoteStop(1000));
+				let cmd = commands::ICP.with(icp::Event::NoteStop(icp::PREVIEW_ID));
 				ctx.submit_command(cmd.to(ctx.window_id()));
 			}
 			_ => {}
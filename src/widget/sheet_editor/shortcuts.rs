@@ -5,18 +5,35 @@ use druid::{
 	Widget,
 };
 
-use super::selection;
+use super::{keyboard::Keyboard, selection};
 
-pub struct Shortcuts;
+pub struct Shortcuts {
+	keyboard: Keyboard,
+}
 
 impl Shortcuts {
 	pub fn new() -> Shortcuts {
-		Shortcuts
+		Shortcuts {
+			keyboard: Keyboard::new(),
+		}
 	}
 }
 
 impl Widget<State> for Shortcuts {
 	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut State, _env: &Env) {
+		// the keys playing notes aren't shortcuts
+		let played = match event {
+			Event::KeyDown(e) => self.keyboard.key_down(ctx, e, data),
+			Event::KeyUp(e) => self.keyboard.key_up(ctx, e, data),
+			Event::Command(cmd) if cmd.is(commands::RECORDING_STOPPED) => {
+				self.keyboard.stop_recording(ctx, data, *cmd.get_unchecked(commands::RECORDING_STOPPED));
+				false
+			}
+			_ => false,
+		};
+		if played {
+			return;
+		}
 		match event {
 			Event::MouseDown(_) => {
 				ctx.request_focus();
@@ -56,8 +73,10 @@ impl Widget<State> for Shortcuts {
 	}
 
 	fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &State, _env: &Env) {
-		if let LifeCycle::WidgetAdded = event {
-		    ctx.register_for_focus();
+		match event {
+			LifeCycle::WidgetAdded => ctx.register_for_focus(),
+			LifeCycle::FocusChanged(false) => self.keyboard.release_all(ctx),
+			_ => {}
 		}
 	}
 